@@ -1,9 +1,12 @@
 use chrono::prelude::*;
 use log::*;
-use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
 use std::fs;
+use std::io::BufReader;
+use strategy::account::*;
+use strategy::backtest::*;
+use strategy::broker::*;
+use strategy::data::*;
 use strategy::granularity::*;
 use strategy::seq::*;
 use strategy::strategy::simple::*;
@@ -13,22 +16,20 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
 
     info!("start load data");
-    let data = fs::read_to_string("./examples/data/EUR_USD_2019-01-01_2019-02-01.json")?;
-    // let data = fs::read_to_string("./examples/data/EUR_USD_2019-01-01_2019-01-02.json")?;
-    // println!("{}", data);
-    let data: serde_json::Value = serde_json::from_str(data.as_str())?;
+    let file = fs::File::open("./examples/data/EUR_USD_2019-01-01_2019-02-01.json")?;
+    // let file = fs::File::open("./examples/data/EUR_USD_2019-01-01_2019-01-02.json")?;
+    let data = load_json_candles::<S5, _>(BufReader::new(file), "S5", "EUR_USD")?;
+    for (start, end) in data.gaps.iter() {
+        let start: DateTime<Utc> = (*start).into();
+        let end: DateTime<Utc> = (*end).into();
+        debug!("gap: {:?} - {:?}", start, end);
+    }
+    for time in data.duplicates.iter() {
+        let time: DateTime<Utc> = (*time).into();
+        warn!("duplicate: {:?}", time);
+    }
     info!("finish load data");
 
-    // let v = get_value_from_data(
-    //     &data,
-    //     "S5",
-    //     "2019-01-01 22:30:00",
-    //     "EUR_USD",
-    //     "bid",
-    //     "close",
-    // );
-    // println!("{:?}", v);
-
     // let start = "2019-01-01T22:00:00Z".parse::<DateTime<Utc>>().unwrap();
     // let end = "2019-01-02T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
     let start = "2019-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
@@ -38,75 +39,19 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     let end = Time::<S5>::new(end.timestamp());
 
     let mut strategy = SimpleSmaCrossStrategy::new(SimpleStrategyBase {}, start, TransactionId(0));
+    let backtester = Backtester::new(
+        start,
+        end,
+        TransactionId(0),
+        "EUR_USD".parse()?,
+        Account::with_currency("USD", 10_000.0, AccountConfig::default()),
+        ExecutionModel::default(),
+    );
 
     info!("start test");
-    for time in start.range_to_end(end) {
-        let dt: DateTime<Utc> = time.into();
-        debug!("{:?}", dt);
-
-        update_source(&mut strategy, &data, dt);
-        strategy.on_tick(dt);
-
-        // if time >= st_start {
-        //     strategy.on_tick(dt);
-        // }
-    }
+    let result = backtester.run(&mut strategy, &data.candles);
+    info!("{}", serde_json::to_string(&result.report)?);
     info!("finish test");
 
     Ok(())
 }
-
-pub fn update_source(
-    strategy: &mut SimpleSmaCrossStrategy,
-    data: &serde_json::Value,
-    dt: DateTime<Utc>,
-) -> Option<()> {
-    let dt_s = dt.format("%Y-%m-%d %H:%M:%S").to_string();
-    let mid_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "mid", "close")?;
-    let bid_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "bid", "close")?;
-    let ask_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "ask", "close")?;
-    // let mid_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "mid", "close");
-    // let bid_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "bid", "close");
-    // let ask_close = get_value_from_data(data, "S5", dt_s.as_str(), "EUR_USD", "ask", "close");
-    strategy.update_source(dt, mid_close, bid_close, ask_close);
-    Some(())
-}
-
-// #[derive(Deserialize)]
-// struct Data {
-//     S5: HashMap<String, >
-// }
-
-pub fn get_value_from_data(
-    data: &serde_json::Value,
-    granularity: &str,
-    time: &str,
-    instrument: &str,
-    type_str: &str,
-    name: &str,
-) -> Option<f64> {
-    data.as_object()?
-        .get(granularity)?
-        .as_object()?
-        .get(time)?
-        .as_object()?
-        .get(instrument)?
-        .as_object()?
-        .get(type_str)?
-        .as_object()?
-        .get(name)?
-        .as_f64()
-}
-
-// def get_value_from_data(data, granularity, time, instrument, type_str, name):
-//     if granularity not in data:
-//         return None
-//     if time not in data[granularity]:
-//         return None
-//     if instrument not in data[granularity][time]:
-//         return None
-//     if type_str not in data[granularity][time][instrument]:
-//         return None
-//     if name not in data[granularity][time][instrument][type_str]:
-//         return None
-//     return data[granularity][time][instrument][type_str][name]
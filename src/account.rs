@@ -0,0 +1,271 @@
+use crate::indicator::stream::Map;
+use crate::indicator::*;
+use crate::ticket::*;
+use crate::transaction::*;
+use crate::Error;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AccountConfig {
+    pub leverage: f64,
+    // NAV が必要証拠金のこの割合を下回るとロスカット
+    pub closeout_ratio: f64,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        Self {
+            leverage: 25.0,
+            closeout_ratio: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AccountState {
+    pub balance: f64,
+    pub unrealized_pl: f64,
+    pub nav: f64,
+    pub margin_used: f64,
+    pub margin_available: f64,
+    pub is_margin_closeout: bool,
+}
+
+// 残高、損益、証拠金は account 通貨建てで持つ
+#[derive(Clone, PartialEq, Debug)]
+pub struct Account {
+    config: AccountConfig,
+    // None なら quote 通貨建ての口座として扱う
+    currency: Option<String>,
+    balance: f64,
+    // quote 通貨 1 単位あたりの account 通貨
+    rate: f64,
+    tickets: TicketBook,
+}
+
+impl Account {
+    pub fn new(balance: f64, config: AccountConfig) -> Self {
+        debug_assert!(config.leverage > 0.0);
+        Self {
+            config: config,
+            currency: None,
+            balance: balance,
+            rate: 1.0,
+            tickets: TicketBook::new(NettingMode::Hedging),
+        }
+    }
+
+    // quote 通貨と違う通貨の口座は、損益と証拠金を計算する前に set_rate でレートを渡す
+    pub fn with_currency(currency: &str, balance: f64, config: AccountConfig) -> Self {
+        Self {
+            currency: Some(currency.to_string()),
+            ..Self::new(balance, config)
+        }
+    }
+
+    pub fn config(&self) -> AccountConfig {
+        self.config
+    }
+
+    pub fn currency(&self) -> Option<&str> {
+        self.currency.as_ref().map(|c| c.as_str())
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.balance
+    }
+
+    pub fn tickets(&self) -> &TicketBook {
+        &self.tickets
+    }
+
+    // 決済時にその時点のレートで換算した実現損益を、約定ごとに手数料を残高に反映する
+    pub fn apply_transaction(&mut self, transaction: SimpleTransaction) -> Result<(), Error> {
+        let pl = match &transaction {
+            SimpleTransaction::CloseOrderFill(t) => match self.tickets.ticket(t.ticket_id) {
+                Some(ticket) => SimpleTicket {
+                    unit: t.unit,
+                    ..ticket
+                }
+                .unrealized_pl(t.price),
+                None => 0.0,
+            },
+            _ => 0.0,
+        };
+        let commission = transaction.commission();
+        self.tickets.try_apply_transaction(transaction)?;
+        self.balance += pl * self.rate - commission;
+        Ok(())
+    }
+
+    pub fn margin_required(&self, unit: usize, price: f64) -> f64 {
+        unit as f64 * price * self.rate / self.config.leverage
+    }
+
+    // 両建ては多い方の unit だけ証拠金がかかる
+    fn margin_used(&self, price: f64) -> f64 {
+        let (long, short) =
+            self.tickets
+                .tickets()
+                .iter()
+                .fold((0, 0), |(l, s), t| match t.long_or_short {
+                    LongOrShort::Long => (l + t.unit, s),
+                    LongOrShort::Short => (l, s + t.unit),
+                });
+        self.margin_required(long.max(short), price)
+    }
+
+    pub fn state(&self, price: f64) -> AccountState {
+        let unrealized_pl = self.tickets.unrealized_pl(price) * self.rate;
+        let nav = self.balance + unrealized_pl;
+        let margin_used = self.margin_used(price);
+        AccountState {
+            balance: self.balance,
+            unrealized_pl: unrealized_pl,
+            nav: nav,
+            margin_used: margin_used,
+            margin_available: (nav - margin_used).max(0.0),
+            is_margin_closeout: margin_used > 0.0 && nav < margin_used * self.config.closeout_ratio,
+        }
+    }
+
+    pub fn can_open(&self, unit: usize, price: f64) -> Result<(), Error> {
+        let required = self.margin_required(unit, price);
+        if required <= self.state(price).margin_available {
+            Ok(())
+        } else {
+            Err(Error::InsufficientMargin(required))
+        }
+    }
+}
+
+pub trait AccountStateIndicator: FuncIndicator<Val = AccountState> + Sized {
+    fn balance(self) -> Map<Self, fn(AccountState) -> f64> {
+        let f: fn(AccountState) -> f64 = |s| s.balance;
+        self.map(f)
+    }
+
+    fn unrealized_pl(self) -> Map<Self, fn(AccountState) -> f64> {
+        let f: fn(AccountState) -> f64 = |s| s.unrealized_pl;
+        self.map(f)
+    }
+
+    fn nav(self) -> Map<Self, fn(AccountState) -> f64> {
+        let f: fn(AccountState) -> f64 = |s| s.nav;
+        self.map(f)
+    }
+
+    fn margin_used(self) -> Map<Self, fn(AccountState) -> f64> {
+        let f: fn(AccountState) -> f64 = |s| s.margin_used;
+        self.map(f)
+    }
+
+    fn margin_available(self) -> Map<Self, fn(AccountState) -> f64> {
+        let f: fn(AccountState) -> f64 = |s| s.margin_available;
+        self.map(f)
+    }
+
+    fn is_margin_closeout(self) -> Map<Self, fn(AccountState) -> bool> {
+        let f: fn(AccountState) -> bool = |s| s.is_margin_closeout;
+        self.map(f)
+    }
+}
+
+impl<I> AccountStateIndicator for I where I: FuncIndicator<Val = AccountState> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::seq::*;
+    use crate::time::*;
+    use crate::transaction::builder::*;
+    use approx::assert_relative_eq;
+    use LongOrShort::*;
+
+    #[test]
+    fn test_account() {
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let config = AccountConfig {
+            leverage: 10.0,
+            closeout_ratio: 0.5,
+        };
+        let mut account = Account::new(100.0, config);
+        assert_eq!(account.can_open(1000, 1.0), Ok(()));
+        assert_eq!(
+            account.can_open(2000, 1.0),
+            Err(Error::InsufficientMargin(200.0))
+        );
+
+        account
+            .apply_transaction(open(offset + 0, time, TicketId(0), 600, 1.0, Long))
+            .unwrap();
+        account
+            .apply_transaction(open(offset + 1, time, TicketId(1), 400, 1.0, Short))
+            .unwrap();
+        let state = account.state(1.1);
+        assert_relative_eq!(state.unrealized_pl, 20.0, max_relative = 0.0000001);
+        assert_relative_eq!(state.nav, 120.0, max_relative = 0.0000001);
+        assert_relative_eq!(state.margin_used, 66.0, max_relative = 0.0000001);
+        assert!(!state.is_margin_closeout);
+
+        account
+            .apply_transaction(close(offset + 2, offset + 1, time, TicketId(1), 400, 1.1))
+            .unwrap();
+        assert_relative_eq!(account.balance(), 60.0, max_relative = 0.0000001);
+
+        // 0.9: nav = 60 - 60 = 0 < 54 * 0.5
+        let state = account.state(0.9);
+        assert_relative_eq!(state.margin_available, 0.0);
+        assert!(state.is_margin_closeout);
+    }
+
+    #[test]
+    fn test_account_currency() {
+        // USD 口座で USD_JPY を取引する
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let mut account = Account::with_currency("USD", 100.0, AccountConfig::default());
+        assert_eq!(account.currency(), Some("USD"));
+        account.set_rate(1.0 / 125.0);
+        // 1000 unit * 125 JPY = 1000 USD を leverage 25 で建てる
+        assert_relative_eq!(account.margin_required(1000, 125.0), 40.0);
+        assert_eq!(account.can_open(1000, 125.0), Ok(()));
+        account
+            .apply_transaction(open(offset + 0, time, TicketId(0), 1000, 125.0, Long))
+            .unwrap();
+
+        account.set_rate(1.0 / 126.0);
+        let state = account.state(126.0);
+        // 1000 JPY の含み益
+        assert_relative_eq!(
+            state.unrealized_pl,
+            1000.0 / 126.0,
+            max_relative = 0.0000001
+        );
+        assert_relative_eq!(state.margin_used, 40.0, max_relative = 0.0000001);
+        account
+            .apply_transaction(close(
+                offset + 1,
+                offset + 0,
+                time,
+                TicketId(0),
+                1000,
+                126.0,
+            ))
+            .unwrap();
+        assert_relative_eq!(
+            account.balance(),
+            100.0 + 1000.0 / 126.0,
+            max_relative = 0.0000001
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use crate::account::*;
+use crate::broker::*;
+use crate::candle::*;
+use crate::data::*;
+use crate::granularity::*;
+use crate::indicator::balance::*;
+use crate::indicator::trade::*;
+use crate::indicator::vec::*;
+use crate::indicator::*;
+use crate::instrument::*;
+use crate::order::*;
+use crate::report::*;
+use crate::seq::*;
+use crate::strategy::*;
+use crate::time::*;
+use crate::transaction::*;
+use chrono::prelude::*;
+use log::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+pub struct BacktestResult {
+    pub transactions: Vec<SimpleTransaction>,
+    pub report: BacktestReport,
+}
+
+// start から end の手前までの足を順に strategy と broker に流す
+pub struct Backtester<G> {
+    start: Time<G>,
+    end: Time<G>,
+    tid_offset: TransactionId,
+    instrument: Instrument,
+    account: Account,
+    model: ExecutionModel,
+}
+
+impl<G> Backtester<G>
+where
+    G: StaticGranularity,
+{
+    pub fn new(
+        start: Time<G>,
+        end: Time<G>,
+        tid_offset: TransactionId,
+        instrument: Instrument,
+        account: Account,
+        model: ExecutionModel,
+    ) -> Self {
+        Self {
+            start: start,
+            end: end,
+            tid_offset: tid_offset,
+            instrument: instrument,
+            account: account,
+            model: model,
+        }
+    }
+
+    // source にデータの無い足は飛ばし、NotFixed になったところで打ち切る
+    // 各足の終値の bid/ask をその足が確定した直後の tick として扱う
+    pub fn run<S, I>(&self, strategy: &mut S, source: &I) -> BacktestResult
+    where
+        S: Strategy<Granularity = G>,
+        I: FuncIndicator<Seq = Time<G>, Val = Option<BidAskCandle>>,
+    {
+        let mut broker = SimulatedBroker::new(
+            self.tid_offset,
+            self.instrument.clone(),
+            self.account.clone(),
+            self.model,
+        );
+        let mut equity = VecIndicator::new(self.start, Vec::new());
+        // 約定は足が確定した時刻 (次の足の始まり) なので換算レートも 1 本後ろにずらして持つ
+        let mut rate = VecIndicator::new(self.start + 1, Vec::new());
+        let mut nav = self.account.balance();
+        let mut time = self.start;
+        let mut tick_id = TickId(0);
+        while time < self.end {
+            let candle = match source.value(time) {
+                Fixed(InRange(Some(c))) => Some(c),
+                Fixed(InRange(None)) | Fixed(OutOfRange) => None,
+                NotFixed => break,
+            };
+            if let Some(candle) = candle {
+                let quote = Quote::new(candle.bid.close, candle.ask.close);
+                strategy.on_bar(time, candle);
+                execute(strategy, &mut broker, tick_id, (time + 1).into(), quote);
+                tick_id = tick_id + 1;
+                nav = broker.account().state(quote.mid()).nav;
+            }
+            equity.add(nav);
+            rate.add(broker.account().rate());
+            time = time + 1;
+        }
+        self.result(&broker, &equity, rate, time)
+    }
+
+    // start から end の手前までの tick を replayer で流す
+    // equity は各足の最後の tick の mid で評価し、tick の無い足は直前の値を使う
+    pub fn run_ticks<S>(&self, strategy: &mut S, replayer: &mut TickReplayer<G>) -> BacktestResult
+    where
+        S: Strategy<Granularity = G>,
+    {
+        let mut broker = SimulatedBroker::new(
+            self.tid_offset,
+            self.instrument.clone(),
+            self.account.clone(),
+            self.model,
+        );
+        let mut equity = VecIndicator::new(self.start, Vec::new());
+        let mut rate = VecIndicator::new(self.start, Vec::new());
+        let mut nav = self.account.balance();
+        let mut time = self.start;
+        let start: DateTime<Utc> = self.start.into();
+        let end: DateTime<Utc> = self.end.into();
+        replayer.skip_until(start);
+        let mut is_pending = false;
+        while let Some(tick) = replayer.peek().copied() {
+            if end <= tick.time {
+                break;
+            }
+            let bar = Time::<G>::truncate(tick.time.timestamp());
+            while time < bar {
+                equity.add(nav);
+                rate.add(broker.account().rate());
+                time = time + 1;
+            }
+            replayer.step(strategy, &mut broker);
+            nav = broker.account().state(tick.mid()).nav;
+            is_pending = true;
+        }
+        replayer.finish(strategy);
+        if is_pending {
+            equity.add(nav);
+            rate.add(broker.account().rate());
+            time = time + 1;
+        }
+        self.result(&broker, &equity, rate, time)
+    }
+
+    // trade の損益は決済した足の換算レートで account 通貨に直す
+    fn result(
+        &self,
+        broker: &SimulatedBroker,
+        equity: &VecIndicator<Time<G>, f64>,
+        rate: VecIndicator<Time<G>, f64>,
+        end: Time<G>,
+    ) -> BacktestResult {
+        let transactions = broker.transactions();
+        let trades = TradeHistories::new(transactions.clone()).into_sync_ptr();
+        let pl = ConvertedProfitLoss::new(trades.clone(), rate);
+        let report = BacktestReport::new(&trades, &pl, self.tid_offset, equity, self.start, end);
+        let transactions = transactions
+            .clone()
+            .into_iter(self.tid_offset)
+            .into_std()
+            .collect();
+        BacktestResult {
+            transactions: transactions,
+            report: report,
+        }
+    }
+}
+
+// tick を時刻順に流し、足が変わったところで確定した足を on_bar に渡す
+// 各 tick に TickId を振り、その時点で確定している最新の足への対応を time に残す
+// 最初の足が確定するまでの tick は足の集計にだけ使い、strategy には渡さない
+pub struct TickReplayer<G> {
+    ticks: Vec<Tick>,
+    index: usize,
+    next: TickId,
+    time: Rc<RefCell<VecIndicator<TickId, Time<G>>>>,
+    current: Option<(Time<G>, BidAskCandle)>,
+    latest: Option<Time<G>>,
+}
+
+impl<G> TickReplayer<G>
+where
+    G: StaticGranularity,
+{
+    pub fn new(mut ticks: Vec<Tick>, offset: TickId) -> Self {
+        ticks.sort_by_key(|t| t.time);
+        Self {
+            ticks: ticks,
+            index: 0,
+            next: offset,
+            time: VecIndicator::new(offset, Vec::new()).into_sync_ptr(),
+            current: None,
+            latest: None,
+        }
+    }
+
+    // TimeToId に渡して確定済みの足の indicator を TickId で引くのに使う
+    pub fn time(&self) -> Rc<RefCell<VecIndicator<TickId, Time<G>>>> {
+        self.time.clone()
+    }
+
+    pub fn peek(&self) -> Option<&Tick> {
+        self.ticks.get(self.index)
+    }
+
+    // time より前の tick を足にも含めずに読み飛ばす
+    pub fn skip_until(&mut self, time: DateTime<Utc>) {
+        while self.peek().map_or(false, |t| t.time < time) {
+            self.index += 1;
+        }
+    }
+
+    // 処理した tick を返し、tick が尽きたら最後の足を確定させて None を返す
+    pub fn step<S>(&mut self, strategy: &mut S, broker: &mut SimulatedBroker) -> Option<Tick>
+    where
+        S: Strategy<Granularity = G>,
+    {
+        let tick = match self.ticks.get(self.index) {
+            Some(t) => *t,
+            None => {
+                self.finish(strategy);
+                return None;
+            }
+        };
+        self.index += 1;
+
+        let bar = Time::<G>::truncate(tick.time.timestamp());
+        self.current = match self.current.take() {
+            Some((b, c)) if b == bar => Some((
+                b,
+                BidAskCandle::new(
+                    extend(c.bid, tick.bid),
+                    extend(c.ask, tick.ask),
+                    extend(c.mid, tick.mid()),
+                ),
+            )),
+            prev => {
+                if let Some((b, c)) = prev {
+                    strategy.on_bar(b, c);
+                    self.latest = Some(b);
+                }
+                let candle = |price| Candle::new(price, price, price, price, 1);
+                Some((
+                    bar,
+                    BidAskCandle::new(candle(tick.bid), candle(tick.ask), candle(tick.mid())),
+                ))
+            }
+        };
+
+        if let Some(latest) = self.latest {
+            let id = self.next;
+            self.next = id + 1;
+            self.time.borrow_mut().add(latest);
+            execute(strategy, broker, id, tick.time, tick.quote());
+        }
+        Some(tick)
+    }
+
+    // 途中までの足を確定させる
+    pub fn finish<S>(&mut self, strategy: &mut S)
+    where
+        S: Strategy<Granularity = G>,
+    {
+        if let Some((b, c)) = self.current.take() {
+            strategy.on_bar(b, c);
+            self.latest = Some(b);
+        }
+    }
+
+    pub fn replay<S>(&mut self, strategy: &mut S, broker: &mut SimulatedBroker)
+    where
+        S: Strategy<Granularity = G>,
+    {
+        while self.step(strategy, broker).is_some() {}
+    }
+}
+
+fn extend(candle: Candle, price: f64) -> Candle {
+    Candle {
+        high: candle.high.max(price),
+        low: candle.low.min(price),
+        close: price,
+        volume: candle.volume + 1,
+        ..candle
+    }
+}
+
+fn execute<S>(
+    strategy: &mut S,
+    broker: &mut SimulatedBroker,
+    tick_id: TickId,
+    time: DateTime<Utc>,
+    quote: Quote,
+) where
+    S: Strategy,
+{
+    broker.set_quote(quote);
+    let orders = strategy.on_tick(tick_id, time, quote, broker.tickets());
+    for order in orders {
+        if let Err(e) = broker.submit(time, order) {
+            warn!("order rejected: {}", e);
+        }
+    }
+    for t in broker.update(time, quote) {
+        strategy.on_transaction(&t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::storage::*;
+    use crate::indicator::tick::*;
+    use crate::ticket::*;
+    use approx::assert_relative_eq;
+
+    // 最初の足で買い、3 本目の足で決済する
+    struct BuyAndClose {
+        bars: usize,
+    }
+
+    impl Strategy for BuyAndClose {
+        type Granularity = S5;
+
+        fn on_bar(&mut self, _time: Time<S5>, _candle: BidAskCandle) {
+            self.bars += 1;
+        }
+
+        fn on_tick(
+            &mut self,
+            _tick_id: TickId,
+            _time: DateTime<Utc>,
+            _quote: Quote,
+            tickets: &TicketBook,
+        ) -> Vec<Order> {
+            match self.bars {
+                1 => vec![Order::market(100, LongOrShort::Long)],
+                3 => tickets
+                    .tickets()
+                    .iter()
+                    .map(|t| Order::close(t.id, t.unit))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_backtest() {
+        let start = Time::<S5>::new(0);
+        let candle = |price: f64| {
+            let c = Candle::new(price, price, price, price, 0);
+            BidAskCandle::new(c, c, c)
+        };
+        let mut source = Storage::new(start);
+        source.add(start + 0, candle(1.0));
+        source.add(start + 2, candle(1.1));
+        source.add(start + 3, candle(1.5));
+        source.add(start + 4, candle(1.4));
+
+        let account = Account::new(1000.0, AccountConfig::default());
+        let backtester = Backtester::new(
+            start,
+            start + 10,
+            TransactionId(0),
+            "EUR_USD".parse().unwrap(),
+            account,
+            ExecutionModel::default(),
+        );
+        let mut strategy = BuyAndClose { bars: 0 };
+        let result = backtester.run(&mut strategy, &source);
+
+        assert_eq!(strategy.bars, 4);
+        assert_eq!(result.transactions.len(), 2);
+        // 足の終値で約定するので時刻は足が確定した時点になる
+        let open_time: DateTime<Utc> = (start + 1).into();
+        assert_eq!(result.transactions[0].time(), open_time);
+        assert_eq!(result.report.trade_count, 1);
+        assert_relative_eq!(result.report.initial_equity, 1000.0);
+        assert_relative_eq!(result.report.final_equity, 1050.0, max_relative = 0.0000001);
+    }
+
+    #[test]
+    fn test_tick_replayer() {
+        let start = Time::<S5>::new(0);
+        let tick = |millis: i64, price: f64| {
+            Tick::new(Utc.timestamp_millis(millis), price - 0.1, price + 0.1, 1)
+        };
+        let ticks = vec![
+            tick(1000, 1.0),
+            tick(11000, 1.2),
+            tick(4999, 1.4),
+            tick(6000, 1.6),
+            tick(21000, 1.8),
+            tick(16000, 1.7),
+            tick(12500, 1.3),
+        ];
+        let mut replayer = TickReplayer::new(ticks, TickId(0));
+        let time = replayer.time();
+        let close = TimeToId::new(
+            VecIndicator::new(start, vec![1.0, 1.1, 1.2, 1.3]),
+            time.clone(),
+        );
+
+        let account = Account::new(1000.0, AccountConfig::default());
+        let backtester = Backtester::new(
+            start,
+            start + 4,
+            TransactionId(0),
+            "EUR_USD".parse().unwrap(),
+            account,
+            ExecutionModel::default(),
+        );
+        let mut strategy = BuyAndClose { bars: 0 };
+        let result = backtester.run_ticks(&mut strategy, &mut replayer);
+
+        // end 以降の tick は流さない
+        assert_eq!(replayer.peek().map(|t| t.time), Some(Utc.timestamp(21, 0)));
+        // 最初の足が確定するまでの 2 つの tick には TickId を振らない
+        let bars = (0..4)
+            .map(|i| time.borrow().value(TickId(i)).unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bars, vec![start, start + 1, start + 1, start + 2]);
+        assert_eq!(time.borrow().value(TickId(4)), NotFixed);
+        assert_eq!(close.value(TickId(2)), Fixed(InRange(1.1)));
+        // 最初の足が確定した tick で買い、3 本目の足が確定した tick で決済する
+        assert_eq!(strategy.bars, 4);
+        assert_eq!(result.transactions.len(), 2);
+        assert_eq!(result.report.trade_count, 1);
+        // 1.7 で買い 1.6 で売るので spread の分だけ減る
+        assert_relative_eq!(result.report.final_equity, 990.0, max_relative = 0.0000001);
+    }
+}
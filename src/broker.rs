@@ -0,0 +1,341 @@
+use crate::account::*;
+use crate::indicator::vec::*;
+use crate::indicator::*;
+use crate::instrument::*;
+use crate::order::*;
+use crate::seq::*;
+use crate::ticket::*;
+use crate::transaction::*;
+use crate::Error;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpreadModel {
+    // 渡された bid/ask をそのまま使う
+    AsQuoted,
+    // mid を中心に固定のスプレッドを付ける
+    Fixed(f64),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ExecutionModel {
+    pub spread: SpreadModel,
+    // 約定価格を不利な方向にずらす幅
+    pub slippage: f64,
+    // 1 unit あたりの手数料
+    pub commission: f64,
+    // 発注から約定判定が始まるまでの遅延
+    pub latency: Duration,
+}
+
+impl Default for ExecutionModel {
+    fn default() -> Self {
+        Self {
+            spread: SpreadModel::AsQuoted,
+            slippage: 0.0,
+            commission: 0.0,
+            latency: Duration::zero(),
+        }
+    }
+}
+
+impl ExecutionModel {
+    fn quote(&self, quote: Quote) -> Quote {
+        let quote = match self.spread {
+            SpreadModel::AsQuoted => quote,
+            SpreadModel::Fixed(spread) => {
+                let mid = quote.mid();
+                Quote::new(mid - spread / 2.0, mid + spread / 2.0)
+            }
+        };
+        Quote::new(quote.bid - self.slippage, quote.ask + self.slippage)
+    }
+}
+
+pub struct SimulatedBroker {
+    instrument: Instrument,
+    model: ExecutionModel,
+    matcher: OrderMatcher,
+    transactions: Rc<RefCell<VecIndicator<TransactionId, SimpleTransaction>>>,
+    account: Account,
+    last_quote: Option<Quote>,
+    commission: f64,
+}
+
+impl SimulatedBroker {
+    pub fn new(
+        tid_offset: TransactionId,
+        instrument: Instrument,
+        account: Account,
+        model: ExecutionModel,
+    ) -> Self {
+        Self {
+            instrument: instrument,
+            model: model,
+            matcher: OrderMatcher::new(tid_offset, TicketId(0)),
+            transactions: VecIndicator::new(tid_offset, Vec::new()).into_sync_ptr(),
+            account: account,
+            last_quote: None,
+            commission: 0.0,
+        }
+    }
+
+    // 約定判定はせずに証拠金の計算に使う quote だけを更新する
+    // account 通貨が instrument の base か quote なら換算レートも quote から求める
+    pub fn set_quote(&mut self, quote: Quote) {
+        self.last_quote = Some(quote);
+        let rate = self
+            .account
+            .currency()
+            .and_then(|c| self.instrument.conversion_rate(c, quote.mid()));
+        if let Some(rate) = rate {
+            self.account.set_rate(rate);
+        }
+    }
+
+    // cross pair の換算レートは別の通貨ペアから求めて渡す
+    pub fn set_rate(&mut self, rate: f64) {
+        self.account.set_rate(rate);
+    }
+
+    // 約定待ちの新規注文と合わせて証拠金が足りなければ受け付けない
+    // quote が無いうちは証拠金を計算できないので新規注文は受け付けない
+    pub fn submit(&mut self, time: DateTime<Utc>, order: Order) -> Result<OrderId, Error> {
+        if let Order::Open(o) = &order {
+            let quote = self.last_quote.ok_or(Error::NoQuote)?;
+            let pending = self
+                .matcher
+                .pending_orders()
+                .iter()
+                .map(|(_, o)| match o {
+                    Order::Open(o) => o.unit,
+                    Order::Close(_) => 0,
+                })
+                .sum::<usize>();
+            self.account.can_open(pending + o.unit, quote.mid())?;
+        }
+        self.matcher.submit_after(order, time + self.model.latency)
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Result<(), Error> {
+        self.matcher.cancel(id)
+    }
+
+    // 約定した transaction を log と口座に反映する
+    // ロスカット水準を下回れば約定待ちの新規注文を取り消し、全 ticket を成行で決済する
+    pub fn update(&mut self, time: DateTime<Utc>, quote: Quote) -> Vec<SimpleTransaction> {
+        self.set_quote(quote);
+        let quote = self.model.quote(quote);
+        let mut transactions = self.matcher.update(time, quote);
+        self.apply(&mut transactions);
+        if self.account.state(quote.mid()).is_margin_closeout {
+            for (id, order) in self.matcher.pending_orders() {
+                if let Order::Open(_) = order {
+                    self.matcher.cancel(id).unwrap();
+                }
+            }
+            for t in self.matcher.tickets().tickets().to_vec() {
+                // ticket の unit は常に正なので受け付けられる
+                self.matcher.submit(Order::close(t.id, t.unit)).unwrap();
+            }
+            let mut closeout = self.matcher.update(time, quote);
+            self.apply(&mut closeout);
+            transactions.extend(closeout);
+        }
+        transactions
+    }
+
+    // 手数料は transaction に残し、log から組み立てた残高にも含まれるようにする
+    fn apply(&mut self, transactions: &mut [SimpleTransaction]) {
+        for t in transactions.iter_mut() {
+            match t {
+                SimpleTransaction::OpenOrderFill(t) => {
+                    t.commission = self.model.commission * t.ticket.unit as f64
+                }
+                SimpleTransaction::CloseOrderFill(t) => {
+                    t.commission = self.model.commission * t.unit as f64
+                }
+                SimpleTransaction::Other(_) => (),
+            }
+            self.commission += t.commission();
+            // matcher が生成した transaction なので口座の ticket と必ず整合する
+            self.account.apply_transaction(t.clone()).unwrap();
+            self.transactions.borrow_mut().add(t.clone());
+        }
+    }
+
+    pub fn transactions(&self) -> Rc<RefCell<VecIndicator<TransactionId, SimpleTransaction>>> {
+        self.transactions.clone()
+    }
+
+    pub fn tickets(&self) -> &TicketBook {
+        self.matcher.tickets()
+    }
+
+    pub fn pending_orders(&self) -> Vec<(OrderId, Order)> {
+        self.matcher.pending_orders()
+    }
+
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.account.balance()
+    }
+
+    pub fn commission(&self) -> f64 {
+        self.commission
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::time::*;
+    use approx::assert_relative_eq;
+    use LongOrShort::*;
+    use MaybeFixed::*;
+
+    #[test]
+    fn test_broker() {
+        let model = ExecutionModel {
+            spread: SpreadModel::Fixed(0.2),
+            slippage: 0.05,
+            commission: 0.01,
+            latency: Duration::seconds(5),
+        };
+        let time = Time::<S5>::new(0);
+        let offset = TransactionId(10);
+        let account = Account::new(1000.0, AccountConfig::default());
+        let eur_usd = "EUR_USD".parse().unwrap();
+        let mut broker = SimulatedBroker::new(offset, eur_usd, account, model);
+
+        assert_eq!(
+            broker.submit((time + 0).into(), Order::market(100, Long)),
+            Err(Error::NoQuote)
+        );
+        broker.set_quote(Quote::new(1.0, 1.0));
+        broker
+            .submit((time + 0).into(), Order::market(100, Long))
+            .unwrap();
+        // 遅延があるので発注した足では約定しない
+        assert!(broker
+            .update((time + 0).into(), Quote::new(1.0, 1.0))
+            .is_empty());
+        let result = broker.update((time + 1).into(), Quote::new(1.0, 1.2));
+        match &result[0] {
+            // mid 1.1 + spread 0.1 + slippage 0.05
+            SimpleTransaction::OpenOrderFill(t) => assert_relative_eq!(t.ticket.price, 1.25),
+            _ => panic!("unexpected transaction"),
+        }
+        assert_relative_eq!(broker.balance(), 999.0);
+
+        broker
+            .submit((time + 1).into(), Order::close(TicketId(0), 100))
+            .unwrap();
+        broker.update((time + 2).into(), Quote::new(1.5, 1.5));
+        // (1.5 - 0.1 - 0.05 - 1.25) * 100 - 1.0
+        assert_relative_eq!(broker.balance(), 1008.0, max_relative = 0.0000001);
+        assert_relative_eq!(broker.commission(), 2.0);
+        assert!(broker.tickets().is_empty());
+
+        // 手数料も log に残るので log から残高を組み立て直せる
+        let transactions = broker.transactions();
+        let mut account = Account::new(1000.0, AccountConfig::default());
+        for i in 0..2 {
+            let t = transactions.borrow().value(offset + i).unwrap().unwrap();
+            assert_relative_eq!(t.commission(), 1.0);
+            account.apply_transaction(t).unwrap();
+        }
+        assert_relative_eq!(account.balance(), broker.balance());
+        assert_eq!(
+            transactions
+                .borrow()
+                .value(offset + 1)
+                .unwrap()
+                .unwrap()
+                .id(),
+            offset + 1
+        );
+        assert_eq!(transactions.borrow().value(offset + 2), NotFixed);
+    }
+
+    #[test]
+    fn test_margin() {
+        let config = AccountConfig {
+            leverage: 10.0,
+            closeout_ratio: 0.5,
+        };
+        let time: DateTime<Utc> = Time::<S5>::new(0).into();
+        let mut broker = SimulatedBroker::new(
+            TransactionId(0),
+            "EUR_USD".parse().unwrap(),
+            Account::new(100.0, config),
+            ExecutionModel::default(),
+        );
+        broker.update(time, Quote::new(1.0, 1.0));
+        assert_eq!(
+            broker.submit(time, Order::market(2000, Long)),
+            Err(Error::InsufficientMargin(200.0))
+        );
+        broker.submit(time, Order::market(900, Long)).unwrap();
+        // 約定待ちの注文の分も証拠金に含める
+        assert_eq!(
+            broker.submit(time, Order::market(200, Long)),
+            Err(Error::InsufficientMargin(110.0))
+        );
+        assert_eq!(broker.update(time, Quote::new(1.0, 1.0)).len(), 1);
+        broker
+            .submit(
+                time,
+                Order::Open(OpenOrder {
+                    order_type: OrderType::Limit(0.5),
+                    unit: 50,
+                    long_or_short: Long,
+                    on_fill: Vec::new(),
+                }),
+            )
+            .unwrap();
+
+        // nav = 100 - 90 = 10 < 81 * 0.5 なのでロスカットされる
+        let result = broker.update(time, Quote::new(0.9, 0.9));
+        assert_eq!(result.len(), 1);
+        assert!(broker.tickets().is_empty());
+        assert!(broker.account().tickets().is_empty());
+        assert!(broker.pending_orders().is_empty());
+        assert_relative_eq!(broker.balance(), 10.0, max_relative = 0.0000001);
+    }
+
+    #[test]
+    fn test_account_currency() {
+        // USD 口座で USD_JPY を取引する
+        let time: DateTime<Utc> = Time::<S5>::new(0).into();
+        let mut broker = SimulatedBroker::new(
+            TransactionId(0),
+            "USD_JPY".parse().unwrap(),
+            Account::with_currency("USD", 100.0, AccountConfig::default()),
+            ExecutionModel::default(),
+        );
+        broker.set_quote(Quote::new(125.0, 125.0));
+        // 2000 USD 分の証拠金 80 USD は残高で足りる
+        broker.submit(time, Order::market(2000, Long)).unwrap();
+        assert_eq!(broker.update(time, Quote::new(125.0, 125.0)).len(), 1);
+        assert_relative_eq!(broker.account().rate(), 0.008);
+
+        broker
+            .submit(time, Order::close(TicketId(0), 2000))
+            .unwrap();
+        broker.update(time, Quote::new(126.0, 126.0));
+        // 2000 JPY の利益を 126 で換算する
+        assert_relative_eq!(
+            broker.balance(),
+            100.0 + 2000.0 / 126.0,
+            max_relative = 0.0000001
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use crate::granularity::*;
+use crate::seq::*;
+use crate::time::*;
+use crate::Error;
+use chrono::prelude::*;
+use std::ops::Add;
+use std::ops::Sub;
+
+const DAY: i64 = 60 * 60 * 24;
+const HOUR: i64 = 60 * 60;
+
+pub trait MarketCalendar: Eq + Ord + Clone + Copy + std::hash::Hash + std::fmt::Debug {
+    fn is_open(t: i64) -> bool;
+
+    // t 以降で最初に市場が開いている時刻
+    fn next_open(t: i64) -> i64;
+
+    // t 以前で最後に市場が開いている時刻
+    fn prev_open(t: i64) -> i64;
+
+    // t 以降で最初に市場が閉まっている時刻
+    fn next_close(t: i64) -> i64;
+}
+
+// 日曜 22:00 (UTC) から金曜 22:00 まで。元日とクリスマスは休み
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct Fx;
+
+impl Fx {
+    fn day_start(t: i64) -> i64 {
+        t - t.rem_euclid(DAY)
+    }
+
+    fn is_holiday(t: i64) -> bool {
+        let dt = Utc.timestamp(t, 0);
+        match (dt.month(), dt.day()) {
+            (1, 1) | (12, 25) => true,
+            _ => false,
+        }
+    }
+
+    fn is_weekend(t: i64) -> bool {
+        let dt = Utc.timestamp(t, 0);
+        match dt.weekday() {
+            Weekday::Fri => dt.hour() >= 22,
+            Weekday::Sat => true,
+            Weekday::Sun => dt.hour() < 22,
+            _ => false,
+        }
+    }
+}
+
+impl MarketCalendar for Fx {
+    fn is_open(t: i64) -> bool {
+        !Self::is_holiday(t) && !Self::is_weekend(t)
+    }
+
+    fn next_open(t: i64) -> i64 {
+        let mut t = t;
+        while !Self::is_open(t) {
+            t = if Self::is_holiday(t) {
+                Self::day_start(t) + DAY
+            } else {
+                let weekday = Utc.timestamp(t, 0).weekday();
+                let days = 6 - weekday.num_days_from_monday() as i64;
+                Self::day_start(t) + DAY * days + HOUR * 22
+            };
+        }
+        t
+    }
+
+    fn prev_open(t: i64) -> i64 {
+        let mut t = t;
+        while !Self::is_open(t) {
+            t = if Self::is_holiday(t) {
+                Self::day_start(t) - 1
+            } else {
+                let weekday = Utc.timestamp(t, 0).weekday();
+                let days = weekday.num_days_from_monday() as i64 - 4;
+                Self::day_start(t) - DAY * days + HOUR * 22 - 1
+            };
+        }
+        t
+    }
+
+    fn next_close(t: i64) -> i64 {
+        if !Self::is_open(t) {
+            return t;
+        }
+        let mut day = Self::day_start(t);
+        loop {
+            if Utc.timestamp(day, 0).weekday() == Weekday::Fri {
+                return day + HOUR * 22;
+            }
+            day += DAY;
+            if Self::is_holiday(day) {
+                return day;
+            }
+        }
+    }
+}
+
+// 市場が開いている足だけを数える Time<G>
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct SessionTime<G, C = Fx>(Time<G>, std::marker::PhantomData<C>);
+
+impl<G, C> SessionTime<G, C>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+{
+    pub fn new(t: i64) -> Self {
+        debug_assert!(C::is_open(t));
+        SessionTime(Time::new(t), std::marker::PhantomData)
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.0.timestamp()
+    }
+
+    pub fn time(&self) -> Time<G> {
+        self.0
+    }
+
+    fn ceil(t: i64) -> i64 {
+        let b = G::truncate(t);
+        if b < t {
+            G::add(b, 1)
+        } else {
+            b
+        }
+    }
+
+    fn forward(t: i64) -> i64 {
+        let mut t = G::add(t, 1);
+        while !C::is_open(t) {
+            t = Self::ceil(C::next_open(t));
+        }
+        t
+    }
+
+    fn backward(t: i64) -> i64 {
+        let mut t = G::add(t, -1);
+        while !C::is_open(t) {
+            t = G::truncate(C::prev_open(t));
+        }
+        t
+    }
+
+    // 閉まっている期間はまとめて飛ばす
+    fn count(from: i64, to: i64) -> i64 {
+        let mut t = from;
+        let mut n = 0;
+        while t < to {
+            if C::is_open(t) {
+                let end = Self::ceil(std::cmp::min(C::next_close(t), to));
+                n += G::distance(end, t);
+                t = end;
+            } else {
+                t = Self::ceil(C::next_open(t));
+            }
+        }
+        n
+    }
+}
+
+impl<G, C> Sequence for SessionTime<G, C>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+{
+    fn distance_from(&self, offset: &Self) -> i64 {
+        if offset.timestamp() <= self.timestamp() {
+            Self::count(offset.timestamp(), self.timestamp())
+        } else {
+            -Self::count(self.timestamp(), offset.timestamp())
+        }
+    }
+}
+
+impl<G, C> Add<i64> for SessionTime<G, C>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+{
+    type Output = SessionTime<G, C>;
+    fn add(self, other: i64) -> Self::Output {
+        if other < 0 {
+            return self - (-other);
+        }
+        let mut t = self.timestamp();
+        for _ in 0..other {
+            t = Self::forward(t);
+        }
+        SessionTime::new(t)
+    }
+}
+
+impl<G, C> Sub<i64> for SessionTime<G, C>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+{
+    type Output = SessionTime<G, C>;
+    fn sub(self, other: i64) -> Self::Output {
+        if other < 0 {
+            return self + (-other);
+        }
+        let mut t = self.timestamp();
+        for _ in 0..other {
+            t = Self::backward(t);
+        }
+        SessionTime::new(t)
+    }
+}
+
+impl<G, C> Into<DateTime<Utc>> for SessionTime<G, C> {
+    fn into(self) -> DateTime<Utc> {
+        self.0.into()
+    }
+}
+
+impl<G, C> std::convert::TryFrom<Time<G>> for SessionTime<G, C>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+{
+    type Error = Error;
+    fn try_from(t: Time<G>) -> Result<Self, Self::Error> {
+        if C::is_open(t.timestamp()) {
+            Ok(SessionTime(t, std::marker::PhantomData))
+        } else {
+            Err(Error::MarketClosed(t.timestamp()))
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub enum Session {
+    Tokyo,
+    London,
+    NewYork,
+}
+
+impl Session {
+    // 夏時間を考慮した UTC からのずれ (秒)
+    pub fn utc_offset(&self, t: i64) -> i64 {
+        let year = Utc.timestamp(t, 0).year();
+        match self {
+            Session::Tokyo => HOUR * 9,
+            // 3 月最終日曜 01:00 UTC から 10 月最終日曜 01:00 UTC まで
+            Session::London => {
+                let begin = last_sunday(year, 3) + HOUR;
+                let end = last_sunday(year, 10) + HOUR;
+                if begin <= t && t < end {
+                    HOUR
+                } else {
+                    0
+                }
+            }
+            // 3 月第 2 日曜 02:00 EST から 11 月第 1 日曜 02:00 EDT まで
+            Session::NewYork => {
+                let begin = nth_sunday(year, 3, 2) + HOUR * 7;
+                let end = nth_sunday(year, 11, 1) + HOUR * 6;
+                if begin <= t && t < end {
+                    -HOUR * 4
+                } else {
+                    -HOUR * 5
+                }
+            }
+        }
+    }
+
+    pub fn local_time(&self, t: i64) -> DateTime<FixedOffset> {
+        FixedOffset::east(self.utc_offset(t) as i32).timestamp(t, 0)
+    }
+
+    // 現地時間での取引時間 [open, close)
+    pub fn hours(&self) -> (u32, u32) {
+        match self {
+            Session::Tokyo => (9, 18),
+            Session::London => (8, 17),
+            Session::NewYork => (8, 17),
+        }
+    }
+
+    pub fn contains(&self, t: i64) -> bool {
+        let local = self.local_time(t);
+        let (open, close) = self.hours();
+        match local.weekday() {
+            Weekday::Sat | Weekday::Sun => false,
+            _ => open <= local.hour() && local.hour() < close,
+        }
+    }
+
+    // 重なっている時間は後から始まったほうを返す
+    pub fn of(t: i64) -> Option<Session> {
+        [Session::NewYork, Session::London, Session::Tokyo]
+            .iter()
+            .find(|s| s.contains(t))
+            .cloned()
+    }
+}
+
+fn nth_sunday(year: i32, month: u32, n: u32) -> i64 {
+    let first = Utc.ymd(year, month, 1);
+    let days = (7 - first.weekday().num_days_from_sunday()) % 7 + (n - 1) * 7;
+    first.and_hms(0, 0, 0).timestamp() + DAY * days as i64
+}
+
+fn last_sunday(year: i32, month: u32) -> i64 {
+    let next = if month == 12 {
+        Utc.ymd(year + 1, 1, 1)
+    } else {
+        Utc.ymd(year, month + 1, 1)
+    };
+    let last = next.pred();
+    let days = last.weekday().num_days_from_sunday();
+    last.and_hms(0, 0, 0).timestamp() - DAY * days as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> i64 {
+        s.parse::<DateTime<Utc>>().unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_fx_is_open() {
+        assert!(Fx::is_open(ts("2019-01-04T21:59:59Z")));
+        assert!(!Fx::is_open(ts("2019-01-04T22:00:00Z")));
+        assert!(!Fx::is_open(ts("2019-01-05T12:00:00Z")));
+        assert!(!Fx::is_open(ts("2019-01-06T21:59:59Z")));
+        assert!(Fx::is_open(ts("2019-01-06T22:00:00Z")));
+        assert!(!Fx::is_open(ts("2019-12-25T12:00:00Z")));
+    }
+
+    #[test]
+    fn test_dst() {
+        // 2019 年の英国夏時間は 3/31 から 10/27、米国夏時間は 3/10 から 11/3
+        assert_eq!(Session::London.utc_offset(ts("2019-03-31T00:59:59Z")), 0);
+        assert_eq!(Session::London.utc_offset(ts("2019-03-31T01:00:00Z")), HOUR);
+        assert_eq!(Session::London.utc_offset(ts("2019-10-27T01:00:00Z")), 0);
+        assert_eq!(
+            Session::NewYork.utc_offset(ts("2019-03-10T06:59:59Z")),
+            -HOUR * 5
+        );
+        assert_eq!(
+            Session::NewYork.utc_offset(ts("2019-03-10T07:00:00Z")),
+            -HOUR * 4
+        );
+        assert_eq!(
+            Session::NewYork.utc_offset(ts("2019-11-03T05:59:59Z")),
+            -HOUR * 4
+        );
+        assert_eq!(
+            Session::NewYork.utc_offset(ts("2019-11-03T06:00:00Z")),
+            -HOUR * 5
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        // 冬は London 08:00 = 08:00 UTC、夏は 07:00 UTC
+        assert!(Session::London.contains(ts("2019-01-07T08:00:00Z")));
+        assert!(!Session::London.contains(ts("2019-07-01T16:00:00Z")));
+        assert!(Session::London.contains(ts("2019-07-01T07:00:00Z")));
+        assert!(Session::Tokyo.contains(ts("2019-07-01T00:00:00Z")));
+        assert_eq!(
+            Session::of(ts("2019-07-01T03:00:00Z")),
+            Some(Session::Tokyo)
+        );
+        assert_eq!(
+            Session::of(ts("2019-07-01T13:00:00Z")),
+            Some(Session::NewYork)
+        );
+        assert_eq!(Session::of(ts("2019-07-01T22:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_skip_weekend() {
+        let t = SessionTime::<M1>::new(ts("2019-01-04T21:58:00Z"));
+        let expect = SessionTime::<M1>::new(ts("2019-01-06T22:01:00Z"));
+        assert_eq!(t + 3, expect);
+        assert_eq!(expect - 3, t);
+        assert_eq!(expect.distance_from(&t), 3);
+        assert_eq!(t.distance_from(&expect), -3);
+    }
+
+    #[test]
+    fn test_skip_holiday() {
+        let t = SessionTime::<D1>::new(ts("2019-12-24T00:00:00Z"));
+        let expect = SessionTime::<D1>::new(ts("2019-12-26T00:00:00Z"));
+        assert_eq!(t + 1, expect);
+        assert_eq!(expect - 1, t);
+
+        // 金曜の次は月曜
+        let t = SessionTime::<D1>::new(ts("2019-12-27T00:00:00Z"));
+        let expect = SessionTime::<D1>::new(ts("2019-12-30T00:00:00Z"));
+        assert_eq!(t + 1, expect);
+        assert_eq!(expect.distance_from(&t), 1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::indicator::stream::Map;
+use crate::indicator::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Candle {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+}
+
+impl Candle {
+    pub fn new(open: f64, high: f64, low: f64, close: f64, volume: i64) -> Self {
+        debug_assert!(low <= open && open <= high);
+        debug_assert!(low <= close && close <= high);
+        Self {
+            open: open,
+            high: high,
+            low: low,
+            close: close,
+            volume: volume,
+        }
+    }
+
+    pub fn typical_price(&self) -> f64 {
+        (self.high + self.low + self.close) / 3.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BidAskCandle {
+    pub bid: Candle,
+    pub ask: Candle,
+    pub mid: Candle,
+}
+
+impl BidAskCandle {
+    pub fn new(bid: Candle, ask: Candle, mid: Candle) -> Self {
+        Self {
+            bid: bid,
+            ask: ask,
+            mid: mid,
+        }
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask.close - self.bid.close
+    }
+}
+
+pub trait CandleIndicator: FuncIndicator<Val = Candle> + Sized {
+    fn open(self) -> Map<Self, fn(Candle) -> f64> {
+        let f: fn(Candle) -> f64 = |c| c.open;
+        self.map(f)
+    }
+
+    fn high(self) -> Map<Self, fn(Candle) -> f64> {
+        let f: fn(Candle) -> f64 = |c| c.high;
+        self.map(f)
+    }
+
+    fn low(self) -> Map<Self, fn(Candle) -> f64> {
+        let f: fn(Candle) -> f64 = |c| c.low;
+        self.map(f)
+    }
+
+    fn close(self) -> Map<Self, fn(Candle) -> f64> {
+        let f: fn(Candle) -> f64 = |c| c.close;
+        self.map(f)
+    }
+
+    fn volume(self) -> Map<Self, fn(Candle) -> i64> {
+        let f: fn(Candle) -> i64 = |c| c.volume;
+        self.map(f)
+    }
+
+    fn typical_price(self) -> Map<Self, fn(Candle) -> f64> {
+        let f: fn(Candle) -> f64 = |c| c.typical_price();
+        self.map(f)
+    }
+}
+
+impl<I> CandleIndicator for I where I: FuncIndicator<Val = Candle> {}
+
+pub trait BidAskCandleIndicator: FuncIndicator<Val = BidAskCandle> + Sized {
+    fn bid(self) -> Map<Self, fn(BidAskCandle) -> Candle> {
+        let f: fn(BidAskCandle) -> Candle = |c| c.bid;
+        self.map(f)
+    }
+
+    fn ask(self) -> Map<Self, fn(BidAskCandle) -> Candle> {
+        let f: fn(BidAskCandle) -> Candle = |c| c.ask;
+        self.map(f)
+    }
+
+    fn mid(self) -> Map<Self, fn(BidAskCandle) -> Candle> {
+        let f: fn(BidAskCandle) -> Candle = |c| c.mid;
+        self.map(f)
+    }
+}
+
+impl<I> BidAskCandleIndicator for I where I: FuncIndicator<Val = BidAskCandle> {}
+
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    use super::*;
+    use crate::ffi::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    pub struct CCandle {
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: i64,
+    }
+
+    impl From<Candle> for CCandle {
+        fn from(c: Candle) -> Self {
+            Self {
+                open: c.open,
+                high: c.high,
+                low: c.low,
+                close: c.close,
+                volume: c.volume,
+            }
+        }
+    }
+
+    impl std::convert::Into<Candle> for CCandle {
+        fn into(self) -> Candle {
+            Candle {
+                open: self.open,
+                high: self.high,
+                low: self.low,
+                close: self.close,
+                volume: self.volume,
+            }
+        }
+    }
+
+    impl From<Option<Candle>> for COption<CCandle> {
+        fn from(c: Option<Candle>) -> Self {
+            c.map(CCandle::from).into()
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    pub struct CBidAskCandle {
+        bid: CCandle,
+        ask: CCandle,
+        mid: CCandle,
+    }
+
+    impl From<BidAskCandle> for CBidAskCandle {
+        fn from(c: BidAskCandle) -> Self {
+            Self {
+                bid: c.bid.into(),
+                ask: c.ask.into(),
+                mid: c.mid.into(),
+            }
+        }
+    }
+
+    impl std::convert::Into<BidAskCandle> for CBidAskCandle {
+        fn into(self) -> BidAskCandle {
+            BidAskCandle {
+                bid: self.bid.into(),
+                ask: self.ask.into(),
+                mid: self.mid.into(),
+            }
+        }
+    }
+
+    impl From<Option<BidAskCandle>> for COption<CBidAskCandle> {
+        fn from(c: Option<BidAskCandle>) -> Self {
+            c.map(CBidAskCandle::from).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::indicator::vec::*;
+    use crate::time::*;
+    use MaybeFixed::*;
+    use MaybeInRange::*;
+
+    #[test]
+    fn test_projection() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![
+            Candle::new(1.0, 2.0, 0.5, 1.5, 10),
+            Candle::new(1.5, 3.0, 1.5, 3.0, 20),
+        ];
+        let source = VecIndicator::new(offset, source).into_sync_ptr();
+
+        let close = source.clone().close();
+        let high = source.clone().high();
+        let typical = source.clone().typical_price();
+        assert_eq!(close.value(offset + 1), Fixed(InRange(3.0)));
+        assert_eq!(high.value(offset + 0), Fixed(InRange(2.0)));
+        assert_eq!(typical.value(offset + 1), Fixed(InRange(2.5)));
+        assert_eq!(close.value(offset + 2), NotFixed);
+    }
+
+    #[test]
+    fn test_bid_ask_projection() {
+        let offset = Time::<S5>::new(0);
+        let bid = Candle::new(1.0, 1.0, 1.0, 1.0, 1);
+        let ask = Candle::new(1.2, 1.2, 1.2, 1.2, 1);
+        let mid = Candle::new(1.1, 1.1, 1.1, 1.1, 1);
+        let source = VecIndicator::new(offset, vec![BidAskCandle::new(bid, ask, mid)]);
+
+        let ask_close = source.ask().close();
+        assert_eq!(ask_close.value(offset), Fixed(InRange(1.2)));
+    }
+}
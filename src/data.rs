@@ -0,0 +1,439 @@
+use crate::candle::*;
+use crate::granularity::*;
+use crate::indicator::storage::*;
+use crate::order::*;
+use crate::time::*;
+use crate::Error;
+use chrono::prelude::*;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::io::Read;
+use std::marker::PhantomData;
+
+pub struct CandleData<G, V> {
+    pub candles: Storage<Time<G>, V>,
+    // 欠けている足の範囲 [start, end)
+    pub gaps: Vec<(Time<G>, Time<G>)>,
+    // 重複した時刻は最初の値を使う
+    pub duplicates: Vec<Time<G>>,
+}
+
+impl<G, V> CandleData<G, V>
+where
+    G: StaticGranularity,
+{
+    pub fn from_records(records: Vec<(Time<G>, V)>) -> Result<Self, Error> {
+        let mut map = BTreeMap::new();
+        let mut duplicates = Vec::new();
+        for (time, value) in records {
+            if map.contains_key(&time) {
+                duplicates.push(time);
+            } else {
+                map.insert(time, value);
+            }
+        }
+        let offset = match map.keys().next() {
+            Some(t) => *t,
+            None => return Err(Error::InvalidData("no candles".to_string())),
+        };
+        let mut candles = Storage::new(offset);
+        let mut gaps = Vec::new();
+        let mut next = offset;
+        for (time, value) in map {
+            if next < time {
+                gaps.push((next, time));
+            }
+            next = time + 1;
+            candles.add(time, value);
+        }
+        Ok(Self {
+            candles: candles,
+            gaps: gaps,
+            duplicates: duplicates,
+        })
+    }
+}
+
+// "2019-01-01 22:30:00.123" (UTC)、RFC3339、unix time を受け付ける
+// 秒未満は省略できる
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(t) = s.parse::<i64>() {
+        return Utc.timestamp_opt(t, 0).single();
+    }
+    if let Ok(t) = s.parse::<f64>() {
+        if !t.is_finite() {
+            return None;
+        }
+        let secs = t.floor();
+        let nanos = ((t - secs) * 1e9).round() as u32;
+        return Utc
+            .timestamp_opt(secs as i64, nanos.min(999_999_999))
+            .single();
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+fn parse_bar_time<G>(s: &str) -> Result<Time<G>, Error>
+where
+    G: StaticGranularity,
+{
+    match parse_time(s) {
+        Some(t) if t.timestamp_subsec_nanos() == 0 => Time::try_from(t),
+        _ => Err(Error::InvalidData(format!("invalid time: {}", s))),
+    }
+}
+
+fn candle(open: f64, high: f64, low: f64, close: f64, volume: i64) -> Result<Candle, Error> {
+    let is_valid = low <= open && open <= high && low <= close && close <= high;
+    if is_valid {
+        Ok(Candle::new(open, high, low, close, volume))
+    } else {
+        Err(Error::InvalidData(format!(
+            "invalid candle: {}, {}, {}, {}",
+            open, high, low, close
+        )))
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonCandle {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: Option<f64>,
+}
+
+impl JsonCandle {
+    fn into_candle(self) -> Result<Candle, Error> {
+        let volume = self.volume.unwrap_or(0.0) as i64;
+        candle(self.open, self.high, self.low, self.close, volume)
+    }
+}
+
+// mid/bid/ask -> candle
+type JsonCandles = HashMap<String, JsonCandle>;
+
+// map の中から key の値だけを seed で読み、他の値は読み飛ばす
+struct Select<'a, T> {
+    key: &'a str,
+    seed: T,
+}
+
+impl<'de, 'a, T> DeserializeSeed<'de> for Select<'a, T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = Option<T::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, T> Visitor<'de> for Select<'a, T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = Option<T::Value>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map containing {}", self.key)
+    }
+
+    // 同じ key が複数あれば最初の値を使う
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut seed = Some(self.seed);
+        let mut value = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match seed.take() {
+                Some(s) if key == self.key => value = Some(map.next_value_seed(s)?),
+                s => {
+                    seed = s;
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+// time -> instrument -> candles のうち instrument の candles だけを集める
+struct JsonBars<'a> {
+    instrument: &'a str,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for JsonBars<'a> {
+    type Value = Vec<(String, JsonCandles)>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for JsonBars<'a> {
+    type Value = Vec<(String, JsonCandles)>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map from time to instruments")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut bars = Vec::new();
+        while let Some(time) = map.next_key::<String>()? {
+            let candles = map.next_value_seed(Select {
+                key: self.instrument,
+                seed: PhantomData::<JsonCandles>,
+            })?;
+            if let Some(candles) = candles {
+                bars.push((time, candles));
+            }
+        }
+        Ok(bars)
+    }
+}
+
+// granularity -> time -> instrument -> mid/bid/ask -> candle の形式
+// 読み込みながら他の granularity と instrument を読み飛ばす
+// mid/bid/ask のどれかが欠けている足は無いものとして扱う
+pub fn load_json_candles<G, R>(
+    reader: R,
+    granularity: &str,
+    instrument: &str,
+) -> Result<CandleData<G, BidAskCandle>, Error>
+where
+    G: StaticGranularity,
+    R: Read,
+{
+    let invalid = |e: serde_json::Error| Error::InvalidData(e.to_string());
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let bars = Select {
+        key: granularity,
+        seed: JsonBars {
+            instrument: instrument,
+        },
+    }
+    .deserialize(&mut deserializer)
+    .map_err(invalid)?;
+    deserializer.end().map_err(invalid)?;
+    // 重複時の結果がファイル内の順序に依らないように時刻の文字列順に並べる
+    let mut bars = bars.unwrap_or_default();
+    bars.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut records = Vec::new();
+    for (time, mut candles) in bars {
+        let (bid, ask, mid) = match (
+            candles.remove("bid"),
+            candles.remove("ask"),
+            candles.remove("mid"),
+        ) {
+            (Some(bid), Some(ask), Some(mid)) => (bid, ask, mid),
+            _ => continue,
+        };
+        let candle = BidAskCandle::new(bid.into_candle()?, ask.into_candle()?, mid.into_candle()?);
+        records.push((parse_bar_time(&time)?, candle));
+    }
+    CandleData::from_records(records)
+}
+
+// time,open,high,low,close[,volume] の形式で、先頭行が数値でなければヘッダとして読み飛ばす
+pub fn load_csv_candles<G, R>(reader: R) -> Result<CandleData<G, Candle>, Error>
+where
+    G: StaticGranularity,
+    R: BufRead,
+{
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::InvalidData(e.to_string()))?;
+        let columns = line.split(',').map(|c| c.trim()).collect::<Vec<_>>();
+        if line.trim().is_empty()
+            || (i == 0 && columns.get(1).map_or(false, |c| c.parse::<f64>().is_err()))
+        {
+            continue;
+        }
+        let invalid = || Error::InvalidData(format!("invalid line {}: {}", i + 1, line));
+        if columns.len() < 5 {
+            return Err(invalid());
+        }
+        let mut prices = [0.0; 4];
+        for (p, c) in prices.iter_mut().zip(columns[1..5].iter()) {
+            *p = c.parse().map_err(|_| invalid())?;
+        }
+        let volume = match columns.get(5) {
+            Some(v) => v.parse::<f64>().map_err(|_| invalid())? as i64,
+            None => 0,
+        };
+        let candle = candle(prices[0], prices[1], prices[2], prices[3], volume)?;
+        records.push((parse_bar_time(columns[0])?, candle));
+    }
+    CandleData::from_records(records)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tick {
+    pub time: DateTime<Utc>,
+    pub bid: f64,
+    pub ask: f64,
+    pub volume: i64,
+}
+
+impl Tick {
+    pub fn new(time: DateTime<Utc>, bid: f64, ask: f64, volume: i64) -> Self {
+        debug_assert!(bid <= ask);
+        Self {
+            time: time,
+            bid: bid,
+            ask: ask,
+            volume: volume,
+        }
+    }
+
+    pub fn quote(&self) -> Quote {
+        Quote::new(self.bid, self.ask)
+    }
+
+    pub fn mid(&self) -> f64 {
+        self.quote().mid()
+    }
+}
+
+// time,bid,ask[,volume] の形式で、先頭行が数値でなければヘッダとして読み飛ばす
+// 同じ時刻の tick はファイル内の順序を保って時刻順に並べる
+pub fn load_csv_ticks<R>(reader: R) -> Result<Vec<Tick>, Error>
+where
+    R: BufRead,
+{
+    let mut ticks = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::InvalidData(e.to_string()))?;
+        let columns = line.split(',').map(|c| c.trim()).collect::<Vec<_>>();
+        if line.trim().is_empty()
+            || (i == 0 && columns.get(1).map_or(false, |c| c.parse::<f64>().is_err()))
+        {
+            continue;
+        }
+        let invalid = || Error::InvalidData(format!("invalid line {}: {}", i + 1, line));
+        if columns.len() < 3 {
+            return Err(invalid());
+        }
+        let time = parse_time(columns[0]).ok_or_else(invalid)?;
+        let bid = columns[1].parse::<f64>().map_err(|_| invalid())?;
+        let ask = columns[2].parse::<f64>().map_err(|_| invalid())?;
+        let volume = match columns.get(3) {
+            Some(v) => v.parse::<f64>().map_err(|_| invalid())? as i64,
+            None => 0,
+        };
+        if !bid.is_finite() || !ask.is_finite() || ask < bid {
+            return Err(invalid());
+        }
+        ticks.push(Tick::new(time, bid, ask, volume));
+    }
+    ticks.sort_by_key(|t| t.time);
+    Ok(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::*;
+    use MaybeFixed::*;
+    use MaybeInRange::*;
+
+    #[test]
+    fn test_load_json() {
+        let c = r#"{"open": 1.0, "high": 1.2, "low": 0.9, "close": 1.1}"#;
+        let bar = |time: &str| {
+            format!(
+                r#""{}": {{"EUR_USD": {{"mid": {}, "bid": {}, "ask": {}}}}}"#,
+                time, c, c, c
+            )
+        };
+        // 他の granularity と instrument は形式が違っても読み飛ばす
+        let json = format!(
+            r#"{{"M1": {{"x": [1, 2]}}, "S5": {{{}, {}, {}, {}, "2019-01-01 00:00:10": {{"USD_JPY": 1}}}}}}"#,
+            bar("2019-01-01 00:00:00"),
+            bar("2019-01-01 00:00:05"),
+            bar("2019-01-01T00:00:05Z"),
+            bar("2019-01-01 00:00:20"),
+        );
+        let data = load_json_candles::<S5, _>(json.as_bytes(), "S5", "EUR_USD").unwrap();
+        let offset = Time::<S5>::try_from(parse_time("2019-01-01 00:00:00").unwrap()).unwrap();
+
+        assert_eq!(data.duplicates, vec![offset + 1]);
+        assert_eq!(data.gaps, vec![(offset + 2, offset + 4)]);
+        assert_eq!(data.candles.value(offset + 2), Fixed(InRange(None)));
+        let candle = data.candles.value(offset + 4).unwrap().unwrap().unwrap();
+        assert_eq!(candle.bid.close, 1.1);
+        assert_eq!(data.candles.value(offset + 5), NotFixed);
+    }
+
+    #[test]
+    fn test_load_csv() {
+        let csv = "time,open,high,low,close,volume\n\
+                   0,1.0,1.2,0.9,1.1,10\n\
+                   10,1.1,1.3,1.0,1.2,5\n\
+                   10,1.1,1.3,1.0,1.2,5\n\
+                   15,1.2,1.2,1.2,1.2\n";
+        let data = load_csv_candles::<S5, _>(csv.as_bytes()).unwrap();
+        let offset = Time::<S5>::new(0);
+        assert_eq!(data.gaps, vec![(offset + 1, offset + 2)]);
+        assert_eq!(data.duplicates, vec![offset + 2]);
+        assert_eq!(
+            data.candles.value(offset + 3),
+            Fixed(InRange(Some(Candle::new(1.2, 1.2, 1.2, 1.2, 0))))
+        );
+
+        let result = load_csv_candles::<S5, _>("0,1.0,0.9,1.2,1.1\n".as_bytes());
+        assert!(result.is_err());
+        let result = load_csv_candles::<S5, _>("3,1.0,1.2,0.9,1.1\n".as_bytes());
+        assert_eq!(result.err(), Some(Error::InvalidTime(3)));
+        // 範囲外の unix time も panic せずに Err を返す
+        let result = load_csv_candles::<S5, _>("9223372036854775807,1.0,1.2,0.9,1.1\n".as_bytes());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_load_csv_ticks() {
+        let csv = "time,bid,ask,volume\n\
+                   2019-01-01 00:00:01.500,1.1,1.2,3\n\
+                   2019-01-01T00:00:00.25Z,1.0,1.1\n\
+                   1546300801.5,1.3,1.4,1\n";
+        let ticks = load_csv_ticks(csv.as_bytes()).unwrap();
+        let time = parse_time("2019-01-01 00:00:00").unwrap();
+        assert_eq!(
+            ticks.iter().map(|t| t.time - time).collect::<Vec<_>>(),
+            vec![
+                chrono::Duration::milliseconds(250),
+                chrono::Duration::milliseconds(1500),
+                chrono::Duration::milliseconds(1500),
+            ]
+        );
+        // 同じ時刻ならファイル内の順序を保つ
+        assert_eq!(ticks[1].volume, 3);
+        assert_eq!(ticks[2].bid, 1.3);
+        assert!(load_csv_ticks("0,1.2,1.1\n".as_bytes()).is_err());
+        assert!(load_csv_ticks("NaN,1.1,1.2\n".as_bytes()).is_err());
+        assert!(load_csv_ticks("0,NaN,1.2\n".as_bytes()).is_err());
+    }
+}
@@ -3,8 +3,24 @@ use chrono::prelude::*;
 pub trait Granularity: Eq + Ord + Clone + Copy + std::hash::Hash + std::fmt::Debug {}
 
 pub trait StaticGranularity: Granularity {
+    // 暦によって長さが変わるものは最短の長さ
     fn unit_duration() -> i64;
     fn is_valid(t: i64) -> bool;
+
+    // t を含む足の開始時刻
+    fn truncate(t: i64) -> i64 {
+        t - t.rem_euclid(Self::unit_duration())
+    }
+
+    // t から n 本進めた足の開始時刻
+    fn add(t: i64, n: i64) -> i64 {
+        t + Self::unit_duration() * n
+    }
+
+    // offset から t まで何本あるか
+    fn distance(t: i64, offset: i64) -> i64 {
+        (t - offset) / Self::unit_duration()
+    }
 }
 
 macro_rules! define_static_granularity {
@@ -30,15 +46,126 @@ define_static_granularity!(S10, 10, |t| {
     let dt = Utc.timestamp(t, 0);
     dt.second() % 10 == 0
 });
+define_static_granularity!(S15, 15, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.second() % 15 == 0
+});
+define_static_granularity!(S30, 30, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.second() % 30 == 0
+});
 define_static_granularity!(M1, 60, |t| {
     let dt = Utc.timestamp(t, 0);
     dt.second() == 0
 });
+define_static_granularity!(M5, 60 * 5, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.minute() % 5 == 0 && dt.second() == 0
+});
+define_static_granularity!(M15, 60 * 15, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.minute() % 15 == 0 && dt.second() == 0
+});
+define_static_granularity!(M30, 60 * 30, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.minute() % 30 == 0 && dt.second() == 0
+});
+define_static_granularity!(H1, 60 * 60, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.minute() == 0 && dt.second() == 0
+});
+define_static_granularity!(H4, 60 * 60 * 4, |t| {
+    let dt = Utc.timestamp(t, 0);
+    dt.hour() % 4 == 0 && dt.minute() == 0 && dt.second() == 0
+});
 define_static_granularity!(D1, 60 * 60 * 24, |t| {
     let dt = Utc.timestamp(t, 0);
     dt.hour() == 0 && dt.minute() == 0 && dt.second() == 0
 });
 
+pub trait WeekStart: Eq + Ord + Clone + Copy + std::hash::Hash + std::fmt::Debug {
+    fn weekday() -> Weekday;
+}
+
+macro_rules! define_week_start {
+    ($t:ident, $w:expr) => {
+        #[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+        pub struct $t;
+        impl WeekStart for $t {
+            fn weekday() -> Weekday {
+                $w
+            }
+        }
+    };
+}
+define_week_start!(Mon, Weekday::Mon);
+define_week_start!(Tue, Weekday::Tue);
+define_week_start!(Wed, Weekday::Wed);
+define_week_start!(Thu, Weekday::Thu);
+define_week_start!(Fri, Weekday::Fri);
+define_week_start!(Sat, Weekday::Sat);
+define_week_start!(Sun, Weekday::Sun);
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct W1<W = Mon>(std::marker::PhantomData<W>);
+impl<W> Granularity for W1<W> where W: WeekStart {}
+impl<W> StaticGranularity for W1<W>
+where
+    W: WeekStart,
+{
+    fn unit_duration() -> i64 {
+        60 * 60 * 24 * 7
+    }
+
+    fn is_valid(t: i64) -> bool {
+        D1::is_valid(t) && Utc.timestamp(t, 0).weekday() == W::weekday()
+    }
+
+    fn truncate(t: i64) -> i64 {
+        let dt = Utc.timestamp(D1::truncate(t), 0);
+        let days =
+            (dt.weekday().num_days_from_monday() + 7 - W::weekday().num_days_from_monday()) % 7;
+        dt.timestamp() - D1::unit_duration() * days as i64
+    }
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct MN;
+impl Granularity for MN {}
+impl MN {
+    fn months(t: i64) -> i64 {
+        let dt = Utc.timestamp(t, 0);
+        dt.year() as i64 * 12 + dt.month0() as i64
+    }
+
+    fn from_months(months: i64) -> i64 {
+        let year = months.div_euclid(12) as i32;
+        let month = months.rem_euclid(12) as u32 + 1;
+        Utc.ymd(year, month, 1).and_hms(0, 0, 0).timestamp()
+    }
+}
+impl StaticGranularity for MN {
+    fn unit_duration() -> i64 {
+        60 * 60 * 24 * 28
+    }
+
+    fn is_valid(t: i64) -> bool {
+        D1::is_valid(t) && Utc.timestamp(t, 0).day() == 1
+    }
+
+    fn truncate(t: i64) -> i64 {
+        Self::from_months(Self::months(t))
+    }
+
+    fn add(t: i64, n: i64) -> i64 {
+        Self::from_months(Self::months(t) + n)
+    }
+
+    fn distance(t: i64, offset: i64) -> i64 {
+        Self::months(t) - Self::months(offset)
+    }
+}
+
 #[cfg(feature = "ffi")]
 pub mod ffi {
     use super::*;
@@ -351,6 +351,21 @@ pub trait FuncIndicator: Indicator {
     {
         rolling::Rolling::new(self, size, f)
     }
+
+    fn rolling_for<T, V, IT, F>(
+        self,
+        time: IT,
+        duration: chrono::Duration,
+        f: F,
+    ) -> rolling::duration::RollingFor<Self, IT, F>
+    where
+        Self: Sized,
+        T: Into<chrono::DateTime<chrono::Utc>>,
+        IT: FuncIndicator<Seq = Self::Seq, Val = T>,
+        F: Fn(rolling::duration::DurationWindow<Self::Seq, Self>) -> MaybeValue<V>,
+    {
+        rolling::duration::RollingFor::new(self, time, duration, f)
+    }
 }
 
 pub trait IterIndicator: Indicator {
@@ -817,6 +832,82 @@ pub mod ffi {
         indicator_value_tid_cross
     );
 
+    use bollinger::ffi::*;
+    use bollinger::*;
+    define_value!(
+        GTime<Var>,
+        CTime,
+        BollingerBandsValue,
+        CBollingerBandsValue,
+        indicator_value_time_bollinger_bands
+    );
+    define_value!(
+        TickId,
+        i64,
+        BollingerBandsValue,
+        CBollingerBandsValue,
+        indicator_value_tick_id_bollinger_bands
+    );
+
+    use stochastic::ffi::*;
+    use stochastic::*;
+    define_value!(
+        GTime<Var>,
+        CTime,
+        StochasticValue,
+        CStochasticValue,
+        indicator_value_time_stochastic
+    );
+    define_value!(
+        TickId,
+        i64,
+        StochasticValue,
+        CStochasticValue,
+        indicator_value_tick_id_stochastic
+    );
+
+    use macd::ffi::*;
+    use macd::*;
+    define_value!(
+        GTime<Var>,
+        CTime,
+        MacdValue,
+        CMacdValue,
+        indicator_value_time_macd
+    );
+    define_value!(
+        TickId,
+        i64,
+        MacdValue,
+        CMacdValue,
+        indicator_value_tick_id_macd
+    );
+
+    use crate::candle::ffi::*;
+    use crate::candle::*;
+    define_value!(GTime<Var>, CTime, Candle, CCandle, indicator_value_time_candle);
+    define_value!(
+        GTime<Var>,
+        CTime,
+        Option<Candle>,
+        COption<CCandle>,
+        indicator_value_time_option_candle
+    );
+    define_value!(
+        GTime<Var>,
+        CTime,
+        BidAskCandle,
+        CBidAskCandle,
+        indicator_value_time_bid_ask_candle
+    );
+    define_value!(
+        GTime<Var>,
+        CTime,
+        Option<BidAskCandle>,
+        COption<CBidAskCandle>,
+        indicator_value_time_option_bid_ask_candle
+    );
+
     // impl Into<i32> for ZoneId {
     //     fn into(self) -> i32 {
     //         self.0
@@ -838,20 +929,39 @@ pub mod ffi {
     use crate::strategy::busena::zone::*;
     define_value!(TickId, i64, ZoneId, i32, indicator_value_tick_id_zone_id);
 
-    // use crate::position::ffi::*;
-    // use crate::position::*;
-    // define_value_convert!(
-    //     SimplePosition,
-    //     CSimplePosition,
-    //     indicator_value_simpleposition
-    // );
-    // use trailing_stop::ffi::*;
-    // use trailing_stop::*;
-    // define_value_convert!(
-    //     TrailingStopSignal,
-    //     CTrailingStopSignal,
-    //     indicator_value_trailingstopsignal
-    // );
+    use crate::position::ffi::*;
+    use crate::position::*;
+    define_value!(
+        GTime<Var>,
+        CTime,
+        SimplePosition,
+        CSimplePosition,
+        indicator_value_time_simple_position
+    );
+    define_value!(
+        TickId,
+        i64,
+        SimplePosition,
+        CSimplePosition,
+        indicator_value_tick_id_simple_position
+    );
+
+    use trailing_stop::ffi::*;
+    use trailing_stop::*;
+    define_value!(
+        GTime<Var>,
+        CTime,
+        TrailingStopSignal,
+        CTrailingStopSignal,
+        indicator_value_time_trailing_stop_signal
+    );
+    define_value!(
+        TickId,
+        i64,
+        TrailingStopSignal,
+        CTrailingStopSignal,
+        indicator_value_tick_id_trailing_stop_signal
+    );
 }
 
 #[cfg(ffi)]
@@ -907,7 +1017,9 @@ pub mod ffi_iter {
     define_via_iter_methods!(f64, via_iter_new_f64, via_iter_destroy_f64);
 }
 
+pub mod account;
 pub mod balance;
+pub mod bollinger;
 pub mod cached;
 pub mod complement;
 pub mod convert_granularity;
@@ -916,14 +1028,23 @@ pub mod count;
 pub mod cross;
 pub mod ema;
 pub mod envelope;
+pub mod equity;
+pub mod macd;
 pub mod ordering;
 pub mod rolling;
+pub mod rsi;
+pub mod session;
 pub mod slope;
 pub mod sma;
+pub mod stddev;
+pub mod stochastic;
 pub mod storage;
 pub mod stream;
 pub mod tick;
+pub mod ticket;
 pub mod trade;
+pub mod trailing_stop;
 pub mod transaction;
 pub mod vec;
-// pub mod trailing_stop;
+pub mod williams_r;
+pub mod zscore;
@@ -0,0 +1,236 @@
+use super::*;
+use crate::account::*;
+use crate::granularity::*;
+use crate::transaction::*;
+use chrono::prelude::*;
+use log::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+// 各足の終わりまでの transaction を反映した口座
+// 決済の損益は transaction の足の rate で account 通貨に換算する
+// clock は transaction を書き込む側が足ごとに進める indicator で、
+// clock が Fixed になった足はその足の終わりまでの transaction が揃っているとみなす
+pub struct AccountSnapshots<G, IT, IC, IR> {
+    transactions: IT,
+    clock: IC,
+    rate: IR,
+    initial: Account,
+    offset: TransactionId,
+    // 取得した足の終わりの口座を時刻順に持ち、過去の足はその手前から積み直す
+    checkpoints: RefCell<Vec<AccountSnapshotsState>>,
+    phantom: std::marker::PhantomData<G>,
+}
+
+#[derive(Clone)]
+struct AccountSnapshotsState {
+    end: DateTime<Utc>,
+    next: TransactionId,
+    account: Account,
+}
+
+impl<G, IT, IC, IR> AccountSnapshots<G, IT, IC, IR> {
+    pub fn new(
+        transactions: IT,
+        clock: IC,
+        rate: IR,
+        account: Account,
+        offset: TransactionId,
+    ) -> Self {
+        Self {
+            transactions: transactions,
+            clock: clock,
+            rate: rate,
+            initial: account,
+            offset: offset,
+            checkpoints: RefCell::new(Vec::new()),
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<G, IT, IC, IR> Indicator for AccountSnapshots<G, IT, IC, IR>
+where
+    G: StaticGranularity,
+    IT: Indicator<Seq = TransactionId>,
+    IC: Indicator<Seq = Time<G>>,
+    IR: Indicator<Seq = Time<G>, Val = f64>,
+{
+    type Seq = Time<G>;
+    type Val = Account;
+}
+
+impl<G, IT, IC, IR> FuncIndicator for AccountSnapshots<G, IT, IC, IR>
+where
+    G: StaticGranularity,
+    IT: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+    IC: FuncIndicator<Seq = Time<G>>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        if let NotFixed = self.clock.value(seq) {
+            return NotFixed;
+        }
+        let end: DateTime<Utc> = (seq + 1).into();
+        let i = self.checkpoints.borrow().partition_point(|c| c.end <= end);
+        let mut state = match i {
+            0 => AccountSnapshotsState {
+                end: end,
+                next: self.offset,
+                account: self.initial.clone(),
+            },
+            _ => {
+                let state = self.checkpoints.borrow()[i - 1].clone();
+                if state.end == end {
+                    return Fixed(InRange(state.account));
+                }
+                state
+            }
+        };
+        loop {
+            // clock が確定していれば、まだ届いていない transaction は次の足以降のもの
+            let transaction = match self.transactions.value(state.next) {
+                Fixed(InRange(t)) if t.time() < end => t,
+                _ => break,
+            };
+            // 市場が閉じている足の rate は直前の値を使う
+            match self
+                .rate
+                .value(Time::truncate(transaction.time().timestamp()))
+            {
+                Fixed(InRange(rate)) => state.account.set_rate(rate),
+                Fixed(OutOfRange) => (),
+                NotFixed => return NotFixed,
+            }
+            // 不正な transaction は無視して処理を続ける
+            if let Err(e) = state.account.apply_transaction(transaction) {
+                warn!("{}", e);
+            }
+            state.next = state.next + 1;
+        }
+        state.end = end;
+        let account = state.account.clone();
+        self.checkpoints.borrow_mut().insert(i, state);
+        Fixed(InRange(account))
+    }
+}
+
+// 各足の終わりの価格と rate で評価した口座の状態
+pub struct AccountHistories<G, IT, IC, IP, IR> {
+    snapshots: AccountSnapshots<G, IT, IC, IR>,
+    price: IP,
+}
+
+impl<G, IT, IC, IP, IR> AccountHistories<G, IT, IC, IP, IR> {
+    pub fn new(
+        transactions: IT,
+        clock: IC,
+        price: IP,
+        rate: IR,
+        account: Account,
+        offset: TransactionId,
+    ) -> Self {
+        Self {
+            snapshots: AccountSnapshots::new(transactions, clock, rate, account, offset),
+            price: price,
+        }
+    }
+}
+
+impl<G, IT, IC, IP, IR> Indicator for AccountHistories<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: Indicator<Seq = TransactionId>,
+    IC: Indicator<Seq = Time<G>>,
+    IP: Indicator<Seq = Time<G>, Val = f64>,
+    IR: Indicator<Seq = Time<G>, Val = f64>,
+{
+    type Seq = Time<G>;
+    type Val = AccountState;
+}
+
+impl<G, IT, IC, IP, IR> FuncIndicator for AccountHistories<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+    IC: FuncIndicator<Seq = Time<G>>,
+    IP: FuncIndicator<Seq = Time<G>, Val = f64>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let price = try_value!(self.price.value(seq));
+        let rate = try_value!(self.snapshots.rate.value(seq));
+        let mut account = try_value!(self.snapshots.value(seq));
+        account.set_rate(rate);
+        Fixed(InRange(account.state(price)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::AccountStateIndicator;
+    use crate::ticket::*;
+    use crate::transaction::builder::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_account_histories() {
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+        let transactions = VecIndicator::new(
+            offset,
+            vec![
+                open(
+                    offset + 0,
+                    time + 1,
+                    TicketId(0),
+                    100,
+                    1.0,
+                    LongOrShort::Long,
+                ),
+                close(offset + 1, offset + 0, time + 3, TicketId(0), 100, 1.5),
+            ],
+        );
+        // 読んだ transaction の数を数える
+        let reads = Rc::new(std::cell::Cell::new(0));
+        let r = reads.clone();
+        let transactions = ClosureIndicator::new(move |seq| {
+            r.set(r.get() + 1);
+            transactions.value(seq)
+        });
+        let price = VecIndicator::new(time, vec![1.0, 1.0, 1.2, 1.5, 1.4]).into_sync_ptr();
+        let account = Account::new(100.0, AccountConfig::default());
+        let rate = ClosureIndicator::new(|_| Fixed(InRange(1.0)));
+        let clock = VecIndicator::new(time, Vec::new()).into_sync_ptr();
+        let histories =
+            AccountHistories::new(transactions, clock.clone(), price, rate, account, offset)
+                .into_sync_ptr();
+
+        // 最後の transaction より後の足も clock が進むまでは確定しない
+        let expect = vec![100.0, 100.0, 120.0, 150.0, 150.0];
+        let nav = histories.clone().nav();
+        let result = (0..5)
+            .map(|i| {
+                assert_eq!(nav.value(time + i), NotFixed);
+                clock.borrow_mut().add(());
+                nav.value(time + i).unwrap().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_relative_eq!(
+            result.as_slice(),
+            expect.as_slice(),
+            max_relative = 0.0000001
+        );
+
+        // 過去の足は取得済みの足の口座から積み直す
+        reads.set(0);
+        let state = histories.value(time + 2).unwrap().unwrap();
+        assert_eq!(reads.get(), 0);
+        assert_relative_eq!(state.balance, 100.0);
+        assert_relative_eq!(state.margin_used, 4.8, max_relative = 0.0000001);
+        let balance = histories.balance();
+        assert_relative_eq!(balance.value(time + 4).unwrap().unwrap(), 150.0);
+    }
+}
@@ -1,9 +1,10 @@
 use super::trade::*;
 use super::*;
-use crate::transaction::*;
+use crate::granularity::*;
 use MaybeFixed::*;
 use MaybeInRange::*;
 
+// 手数料を引いた損益。quote 通貨と account 通貨が同じときに使う
 pub struct ProfitLoss<I> {
     trade_histories: I,
 }
@@ -27,15 +28,53 @@ where
 impl<I> FuncIndicator for ProfitLoss<I>
 where
     I: FuncIndicator<Val = Option<Trade>>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let pl = match try_value!(self.trade_histories.value(seq)) {
+            Some(trade) => trade.profit_loss() - trade.commission,
+            None => 0.0,
+        };
+        Fixed(InRange(pl))
+    }
+}
+
+// 決済時点の換算レートで account 通貨建ての損益に直し、手数料を引く
+// rate は quote 通貨 1 単位あたりの account 通貨
+pub struct ConvertedProfitLoss<G, I, IR> {
+    trade_histories: I,
+    rate: IR,
+    phantom: std::marker::PhantomData<G>,
+}
+
+impl<G, I, IR> ConvertedProfitLoss<G, I, IR> {
+    pub fn new(source: I, rate: IR) -> Self {
+        Self {
+            trade_histories: source,
+            rate: rate,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<G, I, IR> Indicator for ConvertedProfitLoss<G, I, IR>
+where
+    I: Indicator,
+{
+    type Seq = I::Seq;
+    type Val = f64;
+}
+
+impl<G, I, IR> FuncIndicator for ConvertedProfitLoss<G, I, IR>
+where
+    G: StaticGranularity,
+    I: FuncIndicator<Val = Option<Trade>>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
 {
     fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
         let pl = match try_value!(self.trade_histories.value(seq)) {
             Some(trade) => {
-                let distance = match trade.long_or_short {
-                    LongOrShort::Long => trade.close_price - trade.open_price,
-                    LongOrShort::Short => trade.open_price - trade.close_price,
-                };
-                distance * trade.unit as f64
+                let time = Time::truncate(trade.close_time.timestamp());
+                trade.profit_loss() * try_value!(self.rate.value(time)) - trade.commission
             }
             None => 0.0,
         };
@@ -47,6 +86,7 @@ where
 mod tests {
     use super::*;
     use crate::granularity::*;
+    use crate::transaction::*;
     use crate::vec::*;
     use approx::assert_relative_eq;
     use LongOrShort::*;
@@ -67,6 +107,7 @@ mod tests {
                     close_time: (time + 5).into(),
                     open_price: 1.234,
                     close_price: 1.5,
+                    commission: 0.0,
                 }),
                 Some(Trade {
                     unit: 100,
@@ -75,6 +116,7 @@ mod tests {
                     close_time: (time + 9).into(),
                     open_price: 1.4,
                     close_price: 1.1,
+                    commission: 2.0,
                 }),
             ],
         );
@@ -82,7 +124,8 @@ mod tests {
             Fixed(InRange(0.0)),
             Fixed(InRange(0.0)),
             Fixed(InRange(26.6)),
-            Fixed(InRange(30.0)),
+            // 手数料を引く
+            Fixed(InRange(28.0)),
         ];
         let pl = ProfitLoss::new(source);
 
@@ -99,11 +142,78 @@ mod tests {
             sum
         });
         let balance_result = balance.into_std().collect::<Vec<_>>();
-        let balance_expect = vec![0.0, 0.0, 26.6, 56.6];
+        let balance_expect = vec![0.0, 0.0, 26.6, 54.6];
         assert_relative_eq!(
             balance_result.as_slice(),
             balance_expect.as_slice(),
             max_relative = 0.0000001
         );
     }
+
+    #[test]
+    fn test_pl_partial_close() {
+        use crate::indicator::trade::*;
+        use crate::ticket::*;
+        use crate::transaction::builder::*;
+
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+        let source = VecIndicator::new(
+            offset,
+            vec![
+                open(offset + 0, time + 0, TicketId(3), 100, 1.4, Short),
+                close(offset + 1, offset + 0, time + 5, TicketId(3), 30, 1.2),
+                close(offset + 2, offset + 0, time + 5, TicketId(3), 70, 1.5),
+            ],
+        );
+        let expect = vec![
+            Fixed(InRange(0.0)),
+            Fixed(InRange(6.0)),
+            Fixed(InRange(-7.0)),
+        ];
+        let pl = ProfitLoss::new(TradeHistories::new(source));
+
+        let result = (0..3).map(|i| pl.value(offset + i)).collect::<Vec<_>>();
+        assert_relative_eq!(
+            result.as_slice(),
+            expect.as_slice(),
+            max_relative = 0.0000001
+        );
+    }
+
+    #[test]
+    fn test_converted_pl() {
+        use crate::instrument::*;
+
+        // USD 口座で USD_JPY を取引する
+        let usd_jpy: Instrument = "USD_JPY".parse().unwrap();
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let source = VecIndicator::new(
+            offset,
+            vec![
+                None,
+                Some(Trade {
+                    unit: 1000,
+                    long_or_short: Long,
+                    open_time: (time + 0).into(),
+                    close_time: (time + 2).into(),
+                    open_price: 124.0,
+                    close_price: 125.0,
+                    commission: 0.5,
+                }),
+            ],
+        );
+        let price = VecIndicator::new(time, vec![124.0, 124.5, 125.0]);
+        let rate = price.map(move |p| usd_jpy.conversion_rate("USD", p).unwrap());
+        let pl = ConvertedProfitLoss::new(source, rate);
+
+        let result = (0..2).map(|i| pl.value(offset + i)).collect::<Vec<_>>();
+        let expect = vec![Fixed(InRange(0.0)), Fixed(InRange(7.5))];
+        assert_relative_eq!(
+            result.as_slice(),
+            expect.as_slice(),
+            max_relative = 0.0000001
+        );
+    }
 }
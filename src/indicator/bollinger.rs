@@ -0,0 +1,141 @@
+use super::*;
+use crate::indicator::rolling::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BollingerBandsValue {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+type Func<I> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<(f64, f64)>;
+
+pub struct BollingerBands<I>
+where
+    I: Indicator,
+{
+    source: Rolling<I, Func<I>>,
+    k: f64,
+}
+
+impl<I> BollingerBands<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    pub fn new(source: I, period: usize, k: f64) -> Self {
+        Self {
+            source: source.rolling(period, |w| {
+                let mean = try_value!(w.clone().mean());
+                let variance = try_value!(w.variance());
+                Fixed(InRange((mean, variance.sqrt())))
+            }),
+            k: k,
+        }
+    }
+}
+
+impl<I> Indicator for BollingerBands<I>
+where
+    I: Indicator<Val = f64>,
+{
+    type Seq = I::Seq;
+    type Val = BollingerBandsValue;
+}
+
+impl<I> FuncIndicator for BollingerBands<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    #[inline]
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let k = self.k;
+        self.source
+            .value(seq)
+            .map2(|(mean, stddev)| BollingerBandsValue {
+                upper: mean + stddev * k,
+                middle: mean,
+                lower: mean - stddev * k,
+            })
+    }
+}
+
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    pub struct CBollingerBandsValue {
+        upper: f64,
+        middle: f64,
+        lower: f64,
+    }
+
+    impl From<BollingerBandsValue> for CBollingerBandsValue {
+        fn from(v: BollingerBandsValue) -> Self {
+            Self {
+                upper: v.upper,
+                middle: v.middle,
+                lower: v.lower,
+            }
+        }
+    }
+
+    type IPtr<S, V> = Ptr<S, BollingerBandsValue, BollingerBands<FuncIndicatorPtr<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                source: *mut FuncIndicatorPtr<$s, $v>,
+                period: c_int,
+                k: f64,
+            ) -> IPtr<$s, $v> {
+                let source = (*source).clone();
+                let ptr = BollingerBands::new(source, period as usize, k).into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, bollinger_bands_new_time_f64);
+    define_new!(TickId, i64, f64, f64, bollinger_bands_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, bollinger_bands_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, bollinger_bands_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_bollinger_bands() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 2.0, 3.0, 5.0, 5.0];
+        let source = VecIndicator::new(offset, source);
+        let bb = BollingerBands::new(source, 3, 2.0);
+
+        assert_eq!(bb.value(offset + 1), Fixed(OutOfRange));
+        match bb.value(offset + 3) {
+            Fixed(InRange(v)) => {
+                let stddev = (14.0_f64 / 9.0).sqrt();
+                assert_relative_eq!(v.middle, 10.0 / 3.0, epsilon = 1e-12);
+                assert_relative_eq!(v.upper, 10.0 / 3.0 + stddev * 2.0, epsilon = 1e-12);
+                assert_relative_eq!(v.lower, 10.0 / 3.0 - stddev * 2.0, epsilon = 1e-12);
+            }
+            v => panic!("{:?}", v),
+        }
+    }
+}
@@ -1,5 +1,9 @@
 use super::*;
 use crate::granularity::*;
+use crate::indicator::rolling::*;
+use std::ops::Add;
+use std::ops::Div;
+use std::ops::Sub;
 use MaybeFixed::*;
 use MaybeInRange::*;
 
@@ -40,6 +44,111 @@ where
     }
 }
 
+pub struct Resample<G1, G2, I, F> {
+    source: I,
+    func: F,
+    phantom: std::marker::PhantomData<(G1, G2)>,
+}
+
+type Func<I, V> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<V>;
+
+impl<G1, G2, V, I, F> Resample<G1, G2, I, F>
+where
+    G1: StaticGranularity,
+    G2: StaticGranularity,
+    I: FuncIndicator<Seq = Time<G1>>,
+    F: Fn(FixedSizeWindow<Time<G1>, I>) -> MaybeValue<V>,
+{
+    pub fn new(source: I, func: F) -> Self {
+        debug_assert!(G1::unit_duration() < G2::unit_duration());
+        Self {
+            source: source,
+            func: func,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<G1, G2, I> Resample<G1, G2, I, Func<I, I::Val>>
+where
+    G1: StaticGranularity,
+    G2: StaticGranularity,
+    I: FuncIndicator<Seq = Time<G1>>,
+    I::Val: Clone,
+{
+    pub fn first(source: I) -> Self {
+        // 足が完成するまでは NotFixed にするため全体を畳み込む
+        Self::new(source, |w| {
+            match try_value!(w.lfold(None, |first, v| first.or(Some(v)))) {
+                Some(v) => Fixed(InRange(v)),
+                None => Fixed(OutOfRange),
+            }
+        })
+    }
+
+    pub fn last(source: I) -> Self {
+        Self::new(source, |w| w.last())
+    }
+
+    pub fn max(source: I) -> Self
+    where
+        I::Val: PartialOrd,
+    {
+        Self::new(source, |w| w.max())
+    }
+
+    pub fn min(source: I) -> Self
+    where
+        I::Val: PartialOrd,
+    {
+        Self::new(source, |w| w.min())
+    }
+
+    pub fn sum(source: I) -> Self
+    where
+        I::Val: Add<Output = I::Val> + Sub<Output = I::Val> + Default,
+    {
+        Self::new(source, |w| w.sum())
+    }
+
+    pub fn mean(source: I) -> Self
+    where
+        I::Val: Add<Output = I::Val> + Sub<Output = I::Val> + Div<f64, Output = I::Val> + Default,
+    {
+        Self::new(source, |w| w.mean())
+    }
+}
+
+impl<G1, G2, V, I, F> Indicator for Resample<G1, G2, I, F>
+where
+    G1: StaticGranularity,
+    G2: StaticGranularity,
+    V: std::fmt::Debug,
+    I: Indicator<Seq = Time<G1>>,
+    F: Fn(FixedSizeWindow<Time<G1>, I>) -> MaybeValue<V>,
+{
+    type Seq = Time<G2>;
+    type Val = V;
+}
+
+impl<G1, G2, V, I, F> FuncIndicator for Resample<G1, G2, I, F>
+where
+    G1: StaticGranularity,
+    G2: StaticGranularity,
+    V: std::fmt::Debug,
+    I: FuncIndicator<Seq = Time<G1>>,
+    F: Fn(FixedSizeWindow<Time<G1>, I>) -> MaybeValue<V>,
+{
+    fn value(&self, time: Self::Seq) -> MaybeValue<Self::Val> {
+        // time の足に含まれる細かい足を全部集める
+        // 最後の足が NotFixed なら全体も NotFixed になる
+        let begin = Time::<G1>::new(time.timestamp());
+        let end = Time::<G1>::new((time + 1).timestamp());
+        let size = end.distance_from(&begin) as usize;
+        (self.func)(FixedSizeWindow::new(&self.source, begin, size))
+    }
+}
+
 // impl<G1, G2, V, I> Indicator<G2, Option<V>> for ConvertWithNone<G1, G2, I>
 // where
 //     G1: Granularity + Copy,
@@ -88,6 +197,35 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn test_resample_s5_to_m1() {
+        let offset_s5 = Time::<S5>::new(0);
+        let offset_m1 = Time::<M1>::new(0);
+        let source = (0..30).map(|i| i as f64).collect::<Vec<_>>();
+        let source = VecIndicator::new(offset_s5, source).into_sync_ptr();
+
+        let first = Resample::<_, M1, _, _>::first(source.clone());
+        let last = Resample::<_, M1, _, _>::last(source.clone());
+        let max = Resample::<_, M1, _, _>::max(source.clone());
+        let sum = Resample::<_, M1, _, _>::sum(source.clone());
+        let mean = Resample::<_, M1, _, _>::mean(source.clone());
+        let range = Resample::<_, M1, _, _>::new(source.clone(), |w| {
+            w.clone().max().zip2(w.min()).map2(|(h, l)| h - l)
+        });
+
+        assert_eq!(first.value(offset_m1 + 1), Fixed(InRange(12.0)));
+        assert_eq!(last.value(offset_m1 + 1), Fixed(InRange(23.0)));
+        assert_eq!(max.value(offset_m1), Fixed(InRange(11.0)));
+        assert_eq!(sum.value(offset_m1), Fixed(InRange(66.0)));
+        assert_eq!(mean.value(offset_m1), Fixed(InRange(5.5)));
+        assert_eq!(range.value(offset_m1 + 1), Fixed(InRange(11.0)));
+
+        // 2 本目の足はまだ完成していない
+        assert_eq!(last.value(offset_m1 + 2), NotFixed);
+        assert_eq!(first.value(offset_m1 + 2), NotFixed);
+        assert_eq!(last.value(offset_m1 - 1), Fixed(OutOfRange));
+    }
+
     #[test]
     fn test_conv_s10_to_s5() {
         let offset_s5 = Time::<S5>::new(0);
@@ -0,0 +1,324 @@
+use super::account::*;
+use super::*;
+use crate::account::*;
+use crate::granularity::*;
+use crate::transaction::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+// 決済済みの損益と手数料だけを反映した残高
+// rate は quote 通貨 1 単位あたりの account 通貨
+pub struct RealizedBalance<G, IT, IC, IR> {
+    snapshots: AccountSnapshots<G, IT, IC, IR>,
+}
+
+impl<G, IT, IC, IR> RealizedBalance<G, IT, IC, IR> {
+    pub fn new(transactions: IT, clock: IC, rate: IR, balance: f64, offset: TransactionId) -> Self {
+        let account = Account::new(balance, AccountConfig::default());
+        Self {
+            snapshots: AccountSnapshots::new(transactions, clock, rate, account, offset),
+        }
+    }
+}
+
+impl<G, IT, IC, IR> Indicator for RealizedBalance<G, IT, IC, IR>
+where
+    G: StaticGranularity,
+    IT: Indicator<Seq = TransactionId>,
+    IC: Indicator<Seq = Time<G>>,
+    IR: Indicator<Seq = Time<G>, Val = f64>,
+{
+    type Seq = Time<G>;
+    type Val = f64;
+}
+
+impl<G, IT, IC, IR> FuncIndicator for RealizedBalance<G, IT, IC, IR>
+where
+    G: StaticGranularity,
+    IT: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+    IC: FuncIndicator<Seq = Time<G>>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let account = try_value!(self.snapshots.value(seq));
+        Fixed(InRange(account.balance()))
+    }
+}
+
+// 足の終わりの価格で評価した保有 ticket の含み損益
+pub struct UnrealizedPL<G, IT, IC, IP, IR> {
+    histories: AccountHistories<G, IT, IC, IP, IR>,
+}
+
+impl<G, IT, IC, IP, IR> UnrealizedPL<G, IT, IC, IP, IR> {
+    pub fn new(transactions: IT, clock: IC, price: IP, rate: IR, offset: TransactionId) -> Self {
+        let account = Account::new(0.0, AccountConfig::default());
+        Self {
+            histories: AccountHistories::new(transactions, clock, price, rate, account, offset),
+        }
+    }
+}
+
+impl<G, IT, IC, IP, IR> Indicator for UnrealizedPL<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: Indicator<Seq = TransactionId>,
+    IC: Indicator<Seq = Time<G>>,
+    IP: Indicator<Seq = Time<G>, Val = f64>,
+    IR: Indicator<Seq = Time<G>, Val = f64>,
+{
+    type Seq = Time<G>;
+    type Val = f64;
+}
+
+impl<G, IT, IC, IP, IR> FuncIndicator for UnrealizedPL<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+    IC: FuncIndicator<Seq = Time<G>>,
+    IP: FuncIndicator<Seq = Time<G>, Val = f64>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let state = try_value!(self.histories.value(seq));
+        Fixed(InRange(state.unrealized_pl))
+    }
+}
+
+// 残高 + 含み損益
+pub struct Equity<G, IT, IC, IP, IR> {
+    histories: AccountHistories<G, IT, IC, IP, IR>,
+}
+
+impl<G, IT, IC, IP, IR> Equity<G, IT, IC, IP, IR> {
+    pub fn new(
+        transactions: IT,
+        clock: IC,
+        price: IP,
+        rate: IR,
+        balance: f64,
+        offset: TransactionId,
+    ) -> Self {
+        let account = Account::new(balance, AccountConfig::default());
+        Self {
+            histories: AccountHistories::new(transactions, clock, price, rate, account, offset),
+        }
+    }
+}
+
+impl<G, IT, IC, IP, IR> Indicator for Equity<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: Indicator<Seq = TransactionId>,
+    IC: Indicator<Seq = Time<G>>,
+    IP: Indicator<Seq = Time<G>, Val = f64>,
+    IR: Indicator<Seq = Time<G>, Val = f64>,
+{
+    type Seq = Time<G>;
+    type Val = f64;
+}
+
+impl<G, IT, IC, IP, IR> FuncIndicator for Equity<G, IT, IC, IP, IR>
+where
+    G: StaticGranularity,
+    IT: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+    IC: FuncIndicator<Seq = Time<G>>,
+    IP: FuncIndicator<Seq = Time<G>, Val = f64>,
+    IR: FuncIndicator<Seq = Time<G>, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let state = try_value!(self.histories.value(seq));
+        Fixed(InRange(state.nav))
+    }
+}
+
+// start からの最高値に対する下落率
+pub struct Drawdown<S, I> {
+    source: I,
+    start: S,
+    // start から各 seq までの最高値 (取得した seq まで伸ばして使い回す)
+    peaks: RefCell<Vec<Option<f64>>>,
+}
+
+impl<S, I> Drawdown<S, I> {
+    pub fn new(source: I, start: S) -> Self {
+        Self {
+            source: source,
+            start: start,
+            peaks: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl<S, I> Drawdown<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn peak(&self, seq: S) -> MaybeValue<Option<f64>> {
+        let mut peaks = self.peaks.borrow_mut();
+        let n = seq.distance_from(&self.start) as usize;
+        while peaks.len() <= n {
+            let last = peaks.last().copied().flatten();
+            let peak = match self.source.value(self.start + peaks.len() as i64) {
+                Fixed(InRange(v)) => Some(last.map_or(v, |p| p.max(v))),
+                Fixed(OutOfRange) => last,
+                NotFixed => return NotFixed,
+            };
+            peaks.push(peak);
+        }
+        Fixed(InRange(peaks[n]))
+    }
+}
+
+impl<S, I> Indicator for Drawdown<S, I>
+where
+    S: Sequence,
+    I: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = f64;
+}
+
+impl<S, I> FuncIndicator for Drawdown<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        if seq < self.start {
+            return Fixed(OutOfRange);
+        }
+        let value = try_value!(self.source.value(seq));
+        let peak = try_value!(self.peak(seq)).unwrap_or(value);
+        let drawdown = if peak > 0.0 {
+            (peak - value) / peak
+        } else {
+            0.0
+        };
+        Fixed(InRange(drawdown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ticket::*;
+    use crate::transaction::builder::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_equity() {
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let transactions = VecIndicator::new(
+            offset,
+            vec![
+                open(
+                    offset + 0,
+                    time + 1,
+                    TicketId(0),
+                    100,
+                    1.0,
+                    LongOrShort::Short,
+                ),
+                close(offset + 1, offset + 0, time + 3, TicketId(0), 100, 1.3),
+            ],
+        )
+        .into_sync_ptr();
+        let price = VecIndicator::new(time, vec![1.0, 0.8, 1.1, 1.3, 1.2]).into_sync_ptr();
+        let rate = ClosureIndicator::new(|_| Fixed(InRange(1.0))).into_sync_ptr();
+        // 5 本目の足まで transaction が揃っている
+        let clock = VecIndicator::new(time, vec![(); 5]).into_sync_ptr();
+        let balance = RealizedBalance::new(
+            transactions.clone(),
+            clock.clone(),
+            rate.clone(),
+            100.0,
+            offset,
+        );
+        let unrealized_pl = UnrealizedPL::new(
+            transactions.clone(),
+            clock.clone(),
+            price.clone(),
+            rate.clone(),
+            offset,
+        );
+        let equity = Equity::new(transactions, clock, price, rate, 100.0, offset).into_sync_ptr();
+        let drawdown = Drawdown::new(equity.clone(), time);
+
+        // 逆順に取得しても同じ結果になる
+        let result = (0..5)
+            .rev()
+            .map(|i| {
+                let seq = time + i;
+                [
+                    balance.value(seq).unwrap().unwrap(),
+                    unrealized_pl.value(seq).unwrap().unwrap(),
+                    equity.value(seq).unwrap().unwrap(),
+                    drawdown.value(seq).unwrap().unwrap(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let expect = vec![
+            [70.0, 0.0, 70.0, 50.0 / 120.0],
+            [70.0, 0.0, 70.0, 50.0 / 120.0],
+            [100.0, -10.0, 90.0, 30.0 / 120.0],
+            [100.0, 20.0, 120.0, 0.0],
+            [100.0, 0.0, 100.0, 0.0],
+        ];
+        for (r, e) in result.iter().zip(expect.iter()) {
+            assert_relative_eq!(r.as_ref(), e.as_ref(), max_relative = 0.0000001);
+        }
+        assert_eq!(drawdown.value(time - 1), Fixed(OutOfRange));
+        // clock が進んでいない足は確定しない
+        assert_eq!(balance.value(time + 5), NotFixed);
+    }
+
+    #[test]
+    fn test_equity_rate() {
+        // USD 口座で USD_JPY を取引する
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let transactions = VecIndicator::new(
+            offset,
+            vec![
+                open(
+                    offset + 0,
+                    time + 1,
+                    TicketId(0),
+                    1000,
+                    125.0,
+                    LongOrShort::Long,
+                ),
+                close(offset + 1, offset + 0, time + 3, TicketId(0), 1000, 124.0),
+            ],
+        )
+        .into_sync_ptr();
+        let price = VecIndicator::new(time, vec![125.0, 125.0, 126.0, 124.0]).into_sync_ptr();
+        let rate = price.clone().map(|p| 1.0 / p).into_sync_ptr();
+        let clock = VecIndicator::new(time, vec![(); 4]).into_sync_ptr();
+        let balance = RealizedBalance::new(
+            transactions.clone(),
+            clock.clone(),
+            rate.clone(),
+            100.0,
+            offset,
+        );
+        let equity = Equity::new(transactions, clock, price, rate, 100.0, offset);
+
+        // 含み益 1000 JPY を 126 で換算する
+        assert_relative_eq!(
+            equity.value(time + 2).unwrap().unwrap(),
+            100.0 + 1000.0 / 126.0,
+            max_relative = 0.0000001
+        );
+        // 決済した足の rate で損失 1000 JPY を換算する
+        assert_relative_eq!(
+            balance.value(time + 3).unwrap().unwrap(),
+            100.0 - 1000.0 / 124.0,
+            max_relative = 0.0000001
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use super::*;
+use crate::indicator::ema::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdValue {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+pub struct MacdLine<S, I> {
+    fast: Ema<S, f64, I, I>,
+    slow: Ema<S, f64, I, I>,
+}
+
+impl<S, I> Indicator for MacdLine<S, I>
+where
+    S: Sequence,
+    I: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = f64;
+}
+
+impl<S, I> FuncIndicator for MacdLine<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.fast
+            .value(seq)
+            .zip2(self.slow.value(seq))
+            .map2(|(fast, slow)| fast - slow)
+    }
+}
+
+pub type MacdLinePtr<S, I> = Rc<RefCell<MacdLine<S, I>>>;
+pub type SignalLinePtr<S, I> = Rc<RefCell<Ema<S, f64, MacdLinePtr<S, I>, MacdLinePtr<S, I>>>>;
+
+pub struct Macd<S, I> {
+    macd: MacdLinePtr<S, I>,
+    signal: SignalLinePtr<S, I>,
+}
+
+impl<S, I> Macd<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64> + Clone,
+{
+    pub fn new(
+        source: I,
+        fast_period: usize,
+        slow_period: usize,
+        signal_period: usize,
+        accuracy: f64,
+        capacity: usize,
+    ) -> Self {
+        let fast = Ema::new(
+            source.clone(),
+            source.clone(),
+            fast_period,
+            accuracy,
+            capacity,
+        );
+        let slow = Ema::new(source.clone(), source, slow_period, accuracy, capacity);
+        let macd = MacdLine {
+            fast: fast,
+            slow: slow,
+        }
+        .into_sync_ptr();
+        let signal = Ema::new(
+            macd.clone(),
+            macd.clone(),
+            signal_period,
+            accuracy,
+            capacity,
+        )
+        .into_sync_ptr();
+        Self {
+            macd: macd,
+            signal: signal,
+        }
+    }
+
+    // Cross::new(macd.macd_line(), macd.signal_line()) でクロスを判定できる
+    pub fn macd_line(&self) -> MacdLinePtr<S, I> {
+        self.macd.clone()
+    }
+
+    pub fn signal_line(&self) -> SignalLinePtr<S, I> {
+        self.signal.clone()
+    }
+}
+
+impl<S, I> Indicator for Macd<S, I>
+where
+    S: Sequence,
+    I: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = MacdValue;
+}
+
+impl<S, I> FuncIndicator for Macd<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.macd
+            .value(seq)
+            .zip2(self.signal.value(seq))
+            .map2(|(macd, signal)| MacdValue {
+                macd: macd,
+                signal: signal,
+                histogram: macd - signal,
+            })
+    }
+}
+
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    pub struct CMacdValue {
+        macd: f64,
+        signal: f64,
+        histogram: f64,
+    }
+
+    impl From<MacdValue> for CMacdValue {
+        fn from(v: MacdValue) -> Self {
+            Self {
+                macd: v.macd,
+                signal: v.signal,
+                histogram: v.histogram,
+            }
+        }
+    }
+
+    type IPtr<S, V> = Ptr<S, MacdValue, Macd<S, FuncIndicatorPtr<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                source: *mut FuncIndicatorPtr<$s, $v>,
+                fast_period: i32,
+                slow_period: i32,
+                signal_period: i32,
+                accuracy: f64,
+                capacity: i32,
+            ) -> IPtr<$s, $v> {
+                let source = (*source).clone();
+                let ptr = Macd::new(
+                    source,
+                    fast_period as usize,
+                    slow_period as usize,
+                    signal_period as usize,
+                    accuracy,
+                    capacity as usize,
+                )
+                .into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, macd_new_time_f64);
+    define_new!(TickId, i64, f64, f64, macd_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, macd_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, macd_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::indicator::cross::*;
+    use crate::vec::*;
+    use MaybeFixed::*;
+    use MaybeInRange::*;
+
+    #[test]
+    fn test_macd_const() {
+        let offset = Time::<S5>::new(0);
+        let source = VecIndicator::new(offset, vec![1.0; 50]).into_sync_ptr();
+        let macd = Macd::new(source, 3, 5, 3, 0.9, 100);
+
+        let expect = MacdValue {
+            macd: 0.0,
+            signal: 0.0,
+            histogram: 0.0,
+        };
+        assert_eq!(macd.value(offset + 5), Fixed(OutOfRange));
+        assert_eq!(macd.value(offset + 49), Fixed(InRange(expect)));
+    }
+
+    #[test]
+    fn test_macd_cross() {
+        let offset = Time::<S5>::new(0);
+        let source = (0..60)
+            .map(|i| if i < 30 { i as f64 } else { 60.0 - i as f64 })
+            .collect::<Vec<_>>();
+        let source = VecIndicator::new(offset, source).into_sync_ptr();
+        let macd = Macd::new(source, 3, 6, 3, 0.9, 100);
+        let cross = Cross::new(macd.macd_line(), macd.signal_line());
+
+        let histogram = (0..60)
+            .map(|i| match macd.value(offset + i) {
+                Fixed(InRange(v)) => Some(v.histogram),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let crossed = (1..60)
+            .filter(|i| match cross.value(offset + *i) {
+                Fixed(InRange(CrossState::GtToLt)) => true,
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(crossed.len(), 1);
+        let i = crossed[0] as usize;
+        assert!(histogram[i - 1].unwrap() > 0.0);
+        assert!(histogram[i].unwrap() < 0.0);
+    }
+}
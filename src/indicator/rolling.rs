@@ -1,15 +1,59 @@
 use super::*;
+use std::cell::RefCell;
+use std::ops::Add;
+use std::ops::Div;
+use std::ops::Sub;
 use MaybeFixed::*;
 use MaybeInRange::*;
 
+pub mod duration;
+pub mod incremental;
+use incremental::*;
+
 pub trait Window<S, V> {
-    fn sum(self) -> MaybeValue<V::Output>
+    fn sum(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Default;
+
+    fn mean(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Div<f64, Output = V> + Default;
+
+    fn variance(self) -> MaybeValue<f64>
+    where
+        V: Into<f64>;
+
+    fn max(self) -> MaybeValue<V>
+    where
+        V: PartialOrd;
+
+    fn min(self) -> MaybeValue<V>
+    where
+        V: PartialOrd;
+
+    fn argmax(self) -> MaybeValue<usize>
+    where
+        V: PartialOrd;
+
+    fn argmin(self) -> MaybeValue<usize>
     where
-        V: std::ops::Add<V> + Default;
+        V: PartialOrd;
 
-    fn mean(self) -> MaybeValue<V::Output>
+    fn count_if<F>(self, f: F) -> MaybeValue<usize>
     where
-        V: std::ops::Add<V> + Default;
+        F: FnMut(&V) -> bool;
+
+    fn all<F>(self, f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool;
+
+    fn any<F>(self, f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool;
+
+    fn to_vec(self) -> MaybeValue<Vec<V>>;
+
+    fn last(self) -> MaybeValue<V>;
 
     fn lfold<B, F>(self, init: B, f: F) -> MaybeValue<B>
     where
@@ -22,18 +66,55 @@ pub trait Window<S, V> {
     fn value(&self, index: usize) -> MaybeValue<V>;
 }
 
-pub struct FixedSizeWindow<'a, S, I: 'a> {
+pub struct FixedSizeWindow<'a, S, I: 'a>
+where
+    I: Indicator,
+{
     source: &'a I,
     offset: S,
     size: usize,
+    state: Option<&'a RollingState<S, I::Val>>,
 }
 
-impl<'a, S, I> FixedSizeWindow<'a, S, I> {
+impl<'a, S, I> FixedSizeWindow<'a, S, I>
+where
+    I: Indicator,
+{
     pub fn new(source: &'a I, offset: S, size: usize) -> Self {
         Self {
             source: source,
             offset: offset,
             size: size,
+            state: None,
+        }
+    }
+
+    pub fn with_state(
+        source: &'a I,
+        offset: S,
+        size: usize,
+        state: &'a RollingState<S, I::Val>,
+    ) -> Self {
+        Self {
+            source: source,
+            offset: offset,
+            size: size,
+            state: Some(state),
+        }
+    }
+}
+
+impl<'a, S, I> Clone for FixedSizeWindow<'a, S, I>
+where
+    S: Clone,
+    I: Indicator,
+{
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source,
+            offset: self.offset.clone(),
+            size: self.size,
+            state: self.state,
         }
     }
 }
@@ -50,72 +131,205 @@ impl<'a, S, I> FixedSizeWindow<'a, S, I> {
 //     }
 // }
 
-// impl<'a, S, I> Window<S, f64> for FixedSizeWindow<'a, S, I>
-// where
-//     S: Sequence,
-//     I: FuncIndicator<Seq = S, Val = f64>,
-// {
-// }
-
-impl<'a, S, I> Window<S, f64> for FixedSizeWindow<'a, S, I>
+impl<'a, S, V, I> FixedSizeWindow<'a, S, I>
 where
     S: Sequence,
-    I: FuncIndicator<Seq = S, Val = f64>,
+    V: Clone,
+    I: FuncIndicator<Seq = S, Val = V>,
 {
-    fn sum(self) -> MaybeValue<f64> {
+    fn fold<B, F>(&self, init: B, mut f: F) -> MaybeValue<B>
+    where
+        F: FnMut(B, &V) -> B,
+    {
+        if let Some(state) = self.state {
+            return Fixed(InRange(state.values().iter().fold(init, f)));
+        }
         let mut i = self.offset;
-        let mut sum = 0.0;
+        let mut acc = init;
         let end = i + self.size as i64;
         while i < end {
-            sum += try_value!(self.source.value(i));
+            acc = f(acc, &try_value!(self.source.value(i)));
             i = i + 1;
         }
-        Fixed(InRange(sum))
+        Fixed(InRange(acc))
+    }
+
+    // 同じ値が複数ある場合は古い方を返す
+    fn arg_by<F>(&self, mut replace: F) -> MaybeValue<(usize, V)>
+    where
+        F: FnMut(&V, &V) -> bool,
+    {
+        let found = try_value!(self.fold((0, None), |(i, found), v| {
+            let found = match found {
+                Some((j, best)) if !replace(&best, v) => Some((j, best)),
+                _ => Some((i, v.clone())),
+            };
+            (i + 1, found)
+        }));
+        match found.1 {
+            Some(found) => Fixed(InRange(found)),
+            None => Fixed(OutOfRange),
+        }
     }
 
-    fn mean(self) -> MaybeValue<f64> {
+    fn arg_max(&self) -> MaybeValue<(usize, V)>
+    where
+        V: PartialOrd,
+    {
+        match self.state {
+            Some(state) => {
+                let i = state.argmax();
+                Fixed(InRange((i, state.values()[i].clone())))
+            }
+            None => self.arg_by(|best, v| best < v),
+        }
+    }
+
+    fn arg_min(&self) -> MaybeValue<(usize, V)>
+    where
+        V: PartialOrd,
+    {
+        match self.state {
+            Some(state) => {
+                let i = state.argmin();
+                Fixed(InRange((i, state.values()[i].clone())))
+            }
+            None => self.arg_by(|best, v| best > v),
+        }
+    }
+}
+
+impl<'a, S, V, I> Window<S, V> for FixedSizeWindow<'a, S, I>
+where
+    S: Sequence,
+    V: Clone,
+    I: FuncIndicator<Seq = S, Val = V>,
+{
+    fn sum(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Default,
+    {
+        // 差分で更新すると読む順で丸め誤差が変わるので毎回先頭から足す
+        self.fold(V::default(), |acc, v| acc + v.clone())
+    }
+
+    fn mean(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Div<f64, Output = V> + Default,
+    {
         let len = self.size as f64;
         self.sum().map2(|v| v / len)
     }
 
-    fn lfold<B, F>(self, init: B, mut f: F) -> MaybeValue<B>
+    fn variance(self) -> MaybeValue<f64>
     where
-        F: FnMut(B, f64) -> B,
+        V: Into<f64>,
     {
-        let mut i = self.offset;
-        let mut sum = init;
-        let end = i + self.size as i64;
-        while i < end {
-            sum = f(sum, try_value!(self.source.value(i)));
-            i = i + 1;
+        let vec = try_value!(self.fold(Vec::with_capacity(self.size), |mut acc, v| {
+            acc.push(v.clone().into());
+            acc
+        }));
+        let len = vec.len() as f64;
+        let mean = vec.iter().sum::<f64>() / len;
+        let var = vec.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / len;
+        Fixed(InRange(var))
+    }
+
+    fn max(self) -> MaybeValue<V>
+    where
+        V: PartialOrd,
+    {
+        self.arg_max().map2(|(_, v)| v)
+    }
+
+    fn min(self) -> MaybeValue<V>
+    where
+        V: PartialOrd,
+    {
+        self.arg_min().map2(|(_, v)| v)
+    }
+
+    fn argmax(self) -> MaybeValue<usize>
+    where
+        V: PartialOrd,
+    {
+        self.arg_max().map2(|(i, _)| i)
+    }
+
+    fn argmin(self) -> MaybeValue<usize>
+    where
+        V: PartialOrd,
+    {
+        self.arg_min().map2(|(i, _)| i)
+    }
+
+    fn count_if<F>(self, mut f: F) -> MaybeValue<usize>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.fold(0, |acc, v| if f(v) { acc + 1 } else { acc })
+    }
+
+    fn all<F>(self, mut f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.fold(true, |acc, v| acc && f(v))
+    }
+
+    fn any<F>(self, mut f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.fold(false, |acc, v| acc || f(v))
+    }
+
+    fn to_vec(self) -> MaybeValue<Vec<V>> {
+        self.fold(Vec::with_capacity(self.size), |mut acc, v| {
+            acc.push(v.clone());
+            acc
+        })
+    }
+
+    fn last(self) -> MaybeValue<V> {
+        let last = try_value!(self.fold(None, |_, v| Some(v.clone())));
+        match last {
+            Some(v) => Fixed(InRange(v)),
+            None => Fixed(OutOfRange),
         }
-        Fixed(InRange(sum))
+    }
+
+    fn lfold<B, F>(self, init: B, mut f: F) -> MaybeValue<B>
+    where
+        F: FnMut(B, V) -> B,
+    {
+        self.fold(init, |acc, v| f(acc, v.clone()))
     }
 
     fn rfold<B, F>(self, init: B, mut f: F) -> MaybeValue<B>
     where
-        F: FnMut(f64, B) -> B,
+        F: FnMut(V, B) -> B,
     {
-        let mut i = self.offset;
-        let mut sum = init;
-        let end = i + self.size as i64;
-        while i < end {
-            sum = f(try_value!(self.source.value(i)), sum);
-            i = i + 1;
-        }
-        Fixed(InRange(sum))
+        self.fold(init, |acc, v| f(v.clone(), acc))
     }
 
-    fn value(&self, index: usize) -> MaybeValue<f64> {
+    fn value(&self, index: usize) -> MaybeValue<V> {
         debug_assert!(self.size > index);
+        if let Some(state) = self.state {
+            return Fixed(InRange(state.values()[index].clone()));
+        }
         self.source.value(self.offset + index as i64)
     }
 }
 
-pub struct Rolling<I, F> {
+pub struct Rolling<I, F>
+where
+    I: Indicator,
+{
     source: I,
     size: usize,
     func: F,
+    state: RefCell<Option<RollingState<I::Seq, I::Val>>>,
 }
 
 impl<I, V, F> Rolling<I, F>
@@ -128,6 +342,37 @@ where
             source: source,
             size: size,
             func: func,
+            state: RefCell::new(None),
+        }
+    }
+}
+
+impl<I, F> Rolling<I, F>
+where
+    I: FuncIndicator,
+    I::Val: Clone,
+{
+    // seq が 1 つ進んだだけなら前回の状態を使い回す
+    fn update_state(&self, seq: I::Seq) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let mut state = self.state.borrow_mut();
+        match &mut *state {
+            Some(s) if s.end() == seq => return true,
+            Some(s) if s.end() + 1 == seq => match s.advance(&self.source) {
+                Fixed(InRange(_)) => return true,
+                _ => return false,
+            },
+            Some(s) if seq < s.end() => return false,
+            _ => (),
+        }
+        match RollingState::build(&self.source, seq, self.size) {
+            Fixed(InRange(s)) => {
+                *state = Some(s);
+                true
+            }
+            _ => false,
         }
     }
 }
@@ -147,12 +392,25 @@ impl<V, I, F> FuncIndicator for Rolling<I, F>
 where
     V: std::fmt::Debug,
     I: FuncIndicator,
+    I::Val: Clone,
     F: Fn(FixedSizeWindow<I::Seq, I>) -> MaybeValue<V>,
 {
     #[inline]
     fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
-        let w = FixedSizeWindow::new(&self.source, seq + 1 - self.size as i64, self.size);
-        (self.func)(w)
+        let offset = seq + 1 - self.size as i64;
+        if self.update_state(seq) {
+            let state = self.state.borrow();
+            let w = FixedSizeWindow::with_state(
+                &self.source,
+                offset,
+                self.size,
+                state.as_ref().unwrap(),
+            );
+            (self.func)(w)
+        } else {
+            let w = FixedSizeWindow::new(&self.source, offset, self.size);
+            (self.func)(w)
+        }
     }
 }
 
@@ -161,6 +419,7 @@ mod tests {
     use super::*;
     use crate::granularity::*;
     use crate::vec::*;
+    use approx::assert_relative_eq;
 
     #[test]
     fn test_sum() {
@@ -180,6 +439,173 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn test_incremental() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 4.0, 2.0, 8.0, 5.0, 7.0, 3.0, 6.0, 9.0, 0.0];
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(7.0)),
+            Fixed(InRange(14.0)),
+            Fixed(InRange(15.0)),
+            Fixed(InRange(20.0)),
+            Fixed(InRange(15.0)),
+            Fixed(InRange(16.0)),
+            Fixed(InRange(18.0)),
+            Fixed(InRange(15.0)),
+            NotFixed,
+        ];
+        let source = VecIndicator::new(offset, source.clone());
+        let sum = Rolling::new(source, 3, |w| w.sum());
+
+        let result = (0..11).map(|i| sum.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+
+        let result = (0..11)
+            .rev()
+            .map(|i| sum.value(offset + i))
+            .collect::<Vec<_>>();
+        let expect = expect.into_iter().rev().collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_access_order() {
+        let offset = Time::<S5>::new(0);
+        let source = (0..200)
+            .map(|i| 1.1 + ((i * 7919) % 1000) as f64 * 0.0001)
+            .collect::<Vec<_>>();
+        let source = VecIndicator::new(offset, source).into_sync_ptr();
+        let forward = Rolling::new(source.clone(), 25, |w| w.clone().mean().zip2(w.variance()));
+        let random = Rolling::new(source.clone(), 25, |w| w.clone().mean().zip2(w.variance()));
+
+        let expect = (0..200)
+            .map(|i| forward.value(offset + i))
+            .collect::<Vec<_>>();
+        // 37 と 200 は互いに素なので全ての seq を 1 度ずつ飛び飛びに読む
+        let mut result = vec![NotFixed; 200];
+        for i in 0..200 {
+            let j = (i * 37 + 11) % 200;
+            result[j] = random.value(offset + j as i64);
+        }
+        let bits = |v: &Vec<MaybeValue<(f64, f64)>>| {
+            v.iter()
+                .map(|x| x.clone().map2(|(m, v)| (m.to_bits(), v.to_bits())))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bits(&result), bits(&expect));
+        assert_eq!(expect[24].map2(|_| ()), Fixed(InRange(())));
+    }
+
+    #[test]
+    fn test_max_min() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 4.0, 2.0, 8.0, 5.0, 7.0, 3.0, 6.0];
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange((4.0, 1.0))),
+            Fixed(InRange((8.0, 2.0))),
+            Fixed(InRange((8.0, 2.0))),
+            Fixed(InRange((8.0, 5.0))),
+            Fixed(InRange((7.0, 3.0))),
+            Fixed(InRange((7.0, 3.0))),
+        ];
+        let source = VecIndicator::new(offset, source.clone()).into_sync_ptr();
+        let max = Rolling::new(source.clone(), 3, |w| w.max());
+        let min = Rolling::new(source.clone(), 3, |w| w.min());
+
+        let result = (0..8)
+            .map(|i| max.value(offset + i).zip2(min.value(offset + i)))
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_variance() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.13, 1.14, 1.12, 1.15, 1.11, 1.13, 1.16, 1.1];
+        let source = VecIndicator::new(offset, source.clone()).into_sync_ptr();
+        let var = Rolling::new(source.clone(), 4, |w| w.variance());
+        let expect = (0..8)
+            .map(|i| FixedSizeWindow::new(&source, offset + i - 3, 4).variance())
+            .collect::<Vec<_>>();
+
+        let result = (0..8).map(|i| var.value(offset + i)).collect::<Vec<_>>();
+        assert_relative_eq!(result.as_slice(), expect.as_slice(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_argmax() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 4.0, 4.0, 2.0, 5.0, 5.0, 3.0];
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(1)),
+            Fixed(InRange(0)),
+            Fixed(InRange(2)),
+            Fixed(InRange(1)),
+            Fixed(InRange(0)),
+        ];
+        let source = VecIndicator::new(offset, source.clone());
+        let argmax = Rolling::new(source, 3, |w| w.argmax());
+
+        let result = (0..7).map(|i| argmax.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_categorical() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![Some(1.0), None, Some(2.0), Some(3.0), None, None];
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange((1, false, true, Some(2.0)))),
+            Fixed(InRange((1, false, true, Some(3.0)))),
+            Fixed(InRange((1, false, true, None))),
+            Fixed(InRange((2, false, true, None))),
+        ];
+        let source = VecIndicator::new(offset, source.clone()).into_sync_ptr();
+        let count = Rolling::new(source.clone(), 3, |w| w.count_if(|v| v.is_none()));
+        let all = Rolling::new(source.clone(), 3, |w| w.all(|v| v.is_none()));
+        let any = Rolling::new(source.clone(), 3, |w| w.any(|v| v.is_some()));
+        let last = Rolling::new(source.clone(), 3, |w| w.last());
+
+        let result = (0..6)
+            .map(|i| {
+                let seq = offset + i;
+                count
+                    .value(seq)
+                    .zip2(all.value(seq))
+                    .zip2(any.value(seq))
+                    .zip2(last.value(seq))
+                    .map2(|(((c, al), an), l)| (c, al, an, l))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![Some(1.0), None, Some(2.0), Some(3.0)];
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(InRange(vec![Some(1.0), None])),
+            Fixed(InRange(vec![None, Some(2.0)])),
+            Fixed(InRange(vec![Some(2.0), Some(3.0)])),
+            NotFixed,
+        ];
+        let source = VecIndicator::new(offset, source.clone());
+        let to_vec = Rolling::new(source, 2, |w| w.to_vec());
+
+        let result = (0..5).map(|i| to_vec.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
     // #[test]
     // fn test_sma() {
     //     let offset = Time::<S5>::new(0);
@@ -0,0 +1,326 @@
+use super::*;
+use chrono::prelude::*;
+use chrono::Duration;
+
+pub struct DurationWindow<'a, S, I: 'a>(FixedSizeWindow<'a, S, I>)
+where
+    I: Indicator;
+
+impl<'a, S, I> DurationWindow<'a, S, I>
+where
+    I: Indicator,
+{
+    pub fn new(source: &'a I, begin: S, size: usize) -> Self {
+        DurationWindow(FixedSizeWindow::new(source, begin, size))
+    }
+
+    pub fn with_state(
+        source: &'a I,
+        begin: S,
+        size: usize,
+        state: &'a RollingState<S, I::Val>,
+    ) -> Self {
+        DurationWindow(FixedSizeWindow::with_state(source, begin, size, state))
+    }
+}
+
+impl<'a, S, I> Clone for DurationWindow<'a, S, I>
+where
+    S: Clone,
+    I: Indicator,
+{
+    fn clone(&self) -> Self {
+        DurationWindow(self.0.clone())
+    }
+}
+
+impl<'a, S, V, I> Window<S, V> for DurationWindow<'a, S, I>
+where
+    S: Sequence,
+    V: Clone,
+    I: FuncIndicator<Seq = S, Val = V>,
+{
+    fn sum(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Default,
+    {
+        self.0.sum()
+    }
+
+    fn mean(self) -> MaybeValue<V>
+    where
+        V: Add<Output = V> + Sub<Output = V> + Div<f64, Output = V> + Default,
+    {
+        self.0.mean()
+    }
+
+    fn variance(self) -> MaybeValue<f64>
+    where
+        V: Into<f64>,
+    {
+        self.0.variance()
+    }
+
+    fn max(self) -> MaybeValue<V>
+    where
+        V: PartialOrd,
+    {
+        self.0.max()
+    }
+
+    fn min(self) -> MaybeValue<V>
+    where
+        V: PartialOrd,
+    {
+        self.0.min()
+    }
+
+    fn argmax(self) -> MaybeValue<usize>
+    where
+        V: PartialOrd,
+    {
+        self.0.argmax()
+    }
+
+    fn argmin(self) -> MaybeValue<usize>
+    where
+        V: PartialOrd,
+    {
+        self.0.argmin()
+    }
+
+    fn count_if<F>(self, f: F) -> MaybeValue<usize>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.0.count_if(f)
+    }
+
+    fn all<F>(self, f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.0.all(f)
+    }
+
+    fn any<F>(self, f: F) -> MaybeValue<bool>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        self.0.any(f)
+    }
+
+    fn to_vec(self) -> MaybeValue<Vec<V>> {
+        self.0.to_vec()
+    }
+
+    fn last(self) -> MaybeValue<V> {
+        self.0.last()
+    }
+
+    fn lfold<B, F>(self, init: B, f: F) -> MaybeValue<B>
+    where
+        F: FnMut(B, V) -> B,
+    {
+        self.0.lfold(init, f)
+    }
+
+    fn rfold<B, F>(self, init: B, f: F) -> MaybeValue<B>
+    where
+        F: FnMut(V, B) -> B,
+    {
+        self.0.rfold(init, f)
+    }
+
+    fn value(&self, index: usize) -> MaybeValue<V> {
+        self.0.value(index)
+    }
+}
+
+pub struct RollingFor<I, IT, F>
+where
+    I: Indicator,
+{
+    source: I,
+    time: IT,
+    duration: Duration,
+    func: F,
+    state: RefCell<Option<RollingState<I::Seq, I::Val>>>,
+}
+
+impl<T, V, I, IT, F> RollingFor<I, IT, F>
+where
+    T: Into<DateTime<Utc>>,
+    I: FuncIndicator,
+    IT: FuncIndicator<Seq = I::Seq, Val = T>,
+    F: Fn(DurationWindow<I::Seq, I>) -> MaybeValue<V>,
+{
+    pub fn new(source: I, time: IT, duration: Duration, func: F) -> Self {
+        debug_assert!(duration > Duration::zero());
+        Self {
+            source: source,
+            time: time,
+            duration: duration,
+            func: func,
+            state: RefCell::new(None),
+        }
+    }
+}
+
+impl<T, I, IT, F> RollingFor<I, IT, F>
+where
+    T: Into<DateTime<Utc>>,
+    I: FuncIndicator,
+    I::Val: Clone,
+    IT: FuncIndicator<Seq = I::Seq, Val = T>,
+{
+    fn is_expired(&self, seq: I::Seq, threshold: DateTime<Utc>) -> MaybeValue<bool> {
+        let t = try_value!(self.time.value(seq)).into();
+        Fixed(InRange(t <= threshold))
+    }
+
+    // (time(seq) - duration, time(seq)] に入る最初の seq を探す
+    // duration 分の履歴が揃っていなければ OutOfRange
+    fn begin(&self, seq: I::Seq) -> MaybeValue<I::Seq> {
+        let now = try_value!(self.time.value(seq)).into();
+        let threshold = now - self.duration;
+        let cached = match &*self.state.borrow() {
+            Some(s) if s.end() + 1 == seq || s.end() == seq => Some(s.begin()),
+            _ => None,
+        };
+        match cached {
+            // 時刻は単調増加なので前回の begin から前に進めるだけでよい
+            Some(mut begin) => {
+                while try_value!(self.is_expired(begin, threshold)) {
+                    begin = begin + 1;
+                }
+                Fixed(InRange(begin))
+            }
+            None => {
+                let mut begin = seq;
+                while !try_value!(self.is_expired(begin - 1, threshold)) {
+                    begin = begin - 1;
+                }
+                Fixed(InRange(begin))
+            }
+        }
+    }
+
+    fn update_state(&self, seq: I::Seq, begin: I::Seq) -> bool {
+        let size = seq.distance_from(&begin) as usize + 1;
+        let mut state = self.state.borrow_mut();
+        match &mut *state {
+            Some(s) if s.end() == seq => return true,
+            Some(s) if s.end() + 1 == seq => match s.advance_to(&self.source, begin) {
+                Fixed(InRange(_)) => return true,
+                _ => return false,
+            },
+            Some(s) if seq < s.end() => return false,
+            _ => (),
+        }
+        match RollingState::build(&self.source, seq, size) {
+            Fixed(InRange(s)) => {
+                *state = Some(s);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<V, I, IT, F> Indicator for RollingFor<I, IT, F>
+where
+    V: std::fmt::Debug,
+    I: Indicator,
+    F: Fn(DurationWindow<I::Seq, I>) -> MaybeValue<V>,
+{
+    type Seq = I::Seq;
+    type Val = V;
+}
+
+impl<T, V, I, IT, F> FuncIndicator for RollingFor<I, IT, F>
+where
+    T: Into<DateTime<Utc>>,
+    V: std::fmt::Debug,
+    I: FuncIndicator,
+    I::Val: Clone,
+    IT: FuncIndicator<Seq = I::Seq, Val = T>,
+    F: Fn(DurationWindow<I::Seq, I>) -> MaybeValue<V>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let begin = try_value!(self.begin(seq));
+        let size = seq.distance_from(&begin) as usize + 1;
+        if self.update_state(seq, begin) {
+            let state = self.state.borrow();
+            let w = DurationWindow::with_state(&self.source, begin, size, state.as_ref().unwrap());
+            (self.func)(w)
+        } else {
+            let w = DurationWindow::new(&self.source, begin, size);
+            (self.func)(w)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+
+    #[test]
+    fn test_duration_sum() {
+        let offset = TickId(0);
+        let source = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let time = vec![0, 5, 5, 10, 20, 25]
+            .into_iter()
+            .map(Time::<S5>::new)
+            .collect::<Vec<_>>();
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(9.0)),
+            Fixed(InRange(5.0)),
+            Fixed(InRange(11.0)),
+        ];
+        let source = VecIndicator::new(offset, source);
+        let time = VecIndicator::new(offset, time);
+        let sum = source.rolling_for(time, Duration::seconds(10), |w| w.sum());
+
+        let result = (0..6).map(|i| sum.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+
+        let result = (0..6)
+            .rev()
+            .map(|i| sum.value(offset + i))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_duration_count() {
+        let offset = TickId(0);
+        let source = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let time = vec![0, 5, 10, 10, 10, 15]
+            .into_iter()
+            .map(Time::<S5>::new)
+            .collect::<Vec<_>>();
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(InRange(1)),
+            Fixed(InRange(1)),
+            Fixed(InRange(2)),
+            Fixed(InRange(3)),
+            Fixed(InRange(1)),
+        ];
+        let source = VecIndicator::new(offset, source);
+        let time = VecIndicator::new(offset, time);
+        let count = source.rolling_for(time, Duration::seconds(5), |w| w.count_if(|_| true));
+
+        let result = (0..6).map(|i| count.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+}
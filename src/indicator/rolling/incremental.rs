@@ -0,0 +1,137 @@
+use super::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+struct MonotonicQueue<S, V> {
+    end: S,
+    queue: VecDeque<(S, V)>,
+}
+
+pub struct RollingState<S, V> {
+    end: S,
+    values: VecDeque<V>,
+    max: RefCell<Option<MonotonicQueue<S, V>>>,
+    min: RefCell<Option<MonotonicQueue<S, V>>>,
+}
+
+impl<S, V> RollingState<S, V>
+where
+    S: Sequence,
+    V: Clone,
+{
+    pub fn build<I>(source: &I, end: S, size: usize) -> MaybeValue<Self>
+    where
+        I: FuncIndicator<Seq = S, Val = V>,
+    {
+        let begin = end + 1 - size as i64;
+        let mut values = VecDeque::with_capacity(size + 1);
+        let mut i = begin;
+        while i <= end {
+            values.push_back(try_value!(source.value(i)));
+            i = i + 1;
+        }
+        Fixed(InRange(Self {
+            end: end,
+            values: values,
+            max: RefCell::new(None),
+            min: RefCell::new(None),
+        }))
+    }
+
+    pub fn end(&self) -> S {
+        self.end
+    }
+
+    pub fn begin(&self) -> S {
+        self.end + 1 - self.values.len() as i64
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn values(&self) -> &VecDeque<V> {
+        &self.values
+    }
+
+    pub fn advance<I>(&mut self, source: &I) -> MaybeValue<()>
+    where
+        I: FuncIndicator<Seq = S, Val = V>,
+    {
+        let begin = self.begin() + 1;
+        self.advance_to(source, begin)
+    }
+
+    pub fn advance_to<I>(&mut self, source: &I, begin: S) -> MaybeValue<()>
+    where
+        I: FuncIndicator<Seq = S, Val = V>,
+    {
+        debug_assert!(self.begin() <= begin && begin <= self.end + 1);
+        let seq = self.end + 1;
+        let v = try_value!(source.value(seq));
+        self.values.push_back(v);
+        self.end = seq;
+        while self.begin() < begin {
+            self.values.pop_front();
+        }
+        Fixed(InRange(()))
+    }
+
+    // 同じ値が複数ある場合は古い方を返す
+    pub fn argmax(&self) -> usize
+    where
+        V: PartialOrd,
+    {
+        let seq = self.sync_queue(&self.max, |back, v| back < v);
+        seq.distance_from(&self.begin()) as usize
+    }
+
+    pub fn argmin(&self) -> usize
+    where
+        V: PartialOrd,
+    {
+        let seq = self.sync_queue(&self.min, |back, v| back > v);
+        seq.distance_from(&self.begin()) as usize
+    }
+
+    fn sync_queue<F>(&self, cell: &RefCell<Option<MonotonicQueue<S, V>>>, dominated: F) -> S
+    where
+        F: Fn(&V, &V) -> bool,
+    {
+        let push = |queue: &mut VecDeque<(S, V)>, seq: S, v: &V| {
+            while queue.back().map_or(false, |(_, back)| dominated(back, v)) {
+                queue.pop_back();
+            }
+            queue.push_back((seq, v.clone()));
+        };
+
+        let mut cell = cell.borrow_mut();
+        let next = match cell.take() {
+            Some(q) if q.end == self.end => q,
+            Some(mut q) if q.end + 1 == self.end => {
+                push(&mut q.queue, self.end, self.values.back().unwrap());
+                let begin = self.begin();
+                while q.queue.front().map_or(false, |(s, _)| *s < begin) {
+                    q.queue.pop_front();
+                }
+                q.end = self.end;
+                q
+            }
+            _ => {
+                let mut queue = VecDeque::new();
+                let mut seq = self.begin();
+                for v in self.values.iter() {
+                    push(&mut queue, seq, v);
+                    seq = seq + 1;
+                }
+                MonotonicQueue {
+                    end: self.end,
+                    queue: queue,
+                }
+            }
+        };
+        let seq = next.queue.front().unwrap().0;
+        *cell = Some(next);
+        seq
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+use crate::library::lru_cache::LRUCache;
+use std::cell::RefCell;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+pub struct Rsi<S, I> {
+    source: I,
+    period: usize,
+    alpha: f64,
+    actual_period: usize,
+    cache: RefCell<LRUCache<S, (f64, f64)>>,
+}
+
+impl<S, I> Rsi<S, I>
+where
+    S: Sequence,
+{
+    pub fn new(source: I, period: usize, accuracy: f64, capacity: usize) -> Self {
+        assert!(period > 0);
+        let alpha = Self::calc_alpha(period);
+        let actual_period = Self::calc_actual_period(accuracy, alpha);
+        Self {
+            source: source,
+            period: period,
+            alpha: alpha,
+            actual_period: actual_period,
+            cache: RefCell::new(LRUCache::new(capacity)),
+        }
+    }
+
+    // Wilder の平滑化
+    pub fn calc_alpha(period: usize) -> f64 {
+        1.0 / period as f64
+    }
+
+    pub fn calc_actual_period(accuracy: f64, alpha: f64) -> usize {
+        assert!(0.0 < accuracy && accuracy < 1.0);
+        let k = (1.0 - accuracy).ln() / (1.0 - alpha).ln();
+        k.ceil() as usize
+    }
+
+    fn get_cache(&self, seq: S) -> Option<(f64, f64)> {
+        self.cache.borrow_mut().get(&seq).map(|v| v.clone())
+    }
+
+    fn set_cache(&self, seq: S, value: (f64, f64)) {
+        self.cache.borrow_mut().insert(seq, value);
+    }
+}
+
+impl<S, I> Rsi<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn gain_loss(&self, seq: S) -> MaybeValue<(f64, f64)> {
+        let prev = try_value!(self.source.value(seq - 1));
+        let curr = try_value!(self.source.value(seq));
+        let diff = curr - prev;
+        Fixed(InRange((diff.max(0.0), (-diff).max(0.0))))
+    }
+
+    // 最初の平均は period 本分の単純平均
+    fn first(&self, seq: S) -> MaybeValue<(f64, f64)> {
+        let mut gain = 0.0;
+        let mut loss = 0.0;
+        for i in 0..self.period {
+            let (g, l) = try_value!(self.gain_loss(seq - i as i64));
+            gain += g;
+            loss += l;
+        }
+        let n = self.period as f64;
+        Fixed(InRange((gain / n, loss / n)))
+    }
+
+    fn value_recursive(&self, seq: S, remain_times: usize) -> MaybeValue<(f64, f64)> {
+        if remain_times == 0 {
+            return self.first(seq);
+        }
+
+        let cache = self.get_cache(seq);
+        match cache {
+            Some(v) => Fixed(InRange(v)),
+            None => self
+                .value_recursive(seq - 1, remain_times - 1)
+                .zip2(self.gain_loss(seq))
+                .map2(|((prev_gain, prev_loss), (gain, loss))| {
+                    (
+                        prev_gain + (gain - prev_gain) * self.alpha,
+                        prev_loss + (loss - prev_loss) * self.alpha,
+                    )
+                })
+                .map2(|v| {
+                    self.set_cache(seq, v);
+                    v
+                }),
+        }
+    }
+}
+
+impl<S, I> Indicator for Rsi<S, I>
+where
+    S: Sequence,
+    I: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = f64;
+}
+
+impl<S, I> FuncIndicator for Rsi<S, I>
+where
+    S: Sequence,
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let (gain, loss) = try_value!(self.value_recursive(seq, self.actual_period));
+        if gain + loss == 0.0 {
+            Fixed(InRange(50.0))
+        } else {
+            Fixed(InRange(100.0 * gain / (gain + loss)))
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    type IPtr<S, V> = Ptr<S, V, Rsi<S, FuncIndicatorPtr<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                source: *mut FuncIndicatorPtr<$s, $v>,
+                period: i32,
+                accuracy: f64,
+                capacity: i32,
+            ) -> IPtr<$s, $v> {
+                let source = (*source).clone();
+                let ptr =
+                    Rsi::new(source, period as usize, accuracy, capacity as usize).into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, rsi_new_time_f64);
+    define_new!(TickId, i64, f64, f64, rsi_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, rsi_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, rsi_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+
+    #[test]
+    fn test_rsi_warm_up() {
+        let offset = Time::<S5>::new(0);
+        let source = (0..20).map(|i| i as f64).collect::<Vec<_>>();
+        let source = VecIndicator::new(offset, source);
+        let rsi = Rsi::new(source, 2, 0.9, 100);
+
+        // 最初の平均に 3 本、平滑化に 4 本必要
+        let expect = [
+            vec![Fixed(OutOfRange); 6].as_slice(),
+            vec![Fixed(InRange(100.0)); 14].as_slice(),
+        ]
+        .concat();
+        let result = (0..20).map(|i| rsi.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_rsi() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 2.0, 1.0, 3.0, 3.0, 2.0];
+        let source = VecIndicator::new(offset, source);
+        let rsi = Rsi::new(source, 2, 0.5, 100);
+
+        // 平滑化は 1 本だけ
+        // 3: first (0.5, 0.5) -> (1.25, 0.25)
+        // 4: first (1.0, 0.5) -> (0.5, 0.25)
+        // 5: first (1.0, 0.0) -> (0.5, 0.5)
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(100.0 * 1.25 / 1.5)),
+            Fixed(InRange(100.0 * 0.5 / 0.75)),
+            Fixed(InRange(50.0)),
+        ];
+        let result = (0..6).map(|i| rsi.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+}
@@ -0,0 +1,158 @@
+use super::*;
+use crate::calendar::*;
+use crate::granularity::*;
+use chrono::prelude::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+pub struct ToSessionTime<C, I> {
+    source: I,
+    phantom: std::marker::PhantomData<C>,
+}
+
+impl<C, I> ToSessionTime<C, I> {
+    pub fn new(source: I) -> Self {
+        Self {
+            source: source,
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<G, C, I> Indicator for ToSessionTime<C, I>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+    I: Indicator<Seq = Time<G>>,
+{
+    type Seq = SessionTime<G, C>;
+    type Val = I::Val;
+}
+
+impl<G, C, I> FuncIndicator for ToSessionTime<C, I>
+where
+    G: StaticGranularity,
+    C: MarketCalendar,
+    I: FuncIndicator<Seq = Time<G>>,
+{
+    #[inline]
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.source.value(seq.time())
+    }
+}
+
+pub struct IsInSession<S> {
+    session: Session,
+    phantom: std::marker::PhantomData<S>,
+}
+
+pub fn is_in_session<S>(session: Session) -> IsInSession<S> {
+    IsInSession {
+        session: session,
+        phantom: std::marker::PhantomData,
+    }
+}
+
+impl<S> Indicator for IsInSession<S>
+where
+    S: Sequence + Into<DateTime<Utc>>,
+{
+    type Seq = S;
+    type Val = bool;
+}
+
+impl<S> FuncIndicator for IsInSession<S>
+where
+    S: Sequence + Into<DateTime<Utc>>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let dt: DateTime<Utc> = seq.into();
+        Fixed(InRange(self.session.contains(dt.timestamp())))
+    }
+}
+
+pub struct SessionOf<S> {
+    phantom: std::marker::PhantomData<S>,
+}
+
+pub fn session_of<S>() -> SessionOf<S> {
+    SessionOf {
+        phantom: std::marker::PhantomData,
+    }
+}
+
+impl<S> Indicator for SessionOf<S>
+where
+    S: Sequence + Into<DateTime<Utc>>,
+{
+    type Seq = S;
+    type Val = Option<Session>;
+}
+
+impl<S> FuncIndicator for SessionOf<S>
+where
+    S: Sequence + Into<DateTime<Utc>>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let dt: DateTime<Utc> = seq.into();
+        Fixed(InRange(Session::of(dt.timestamp())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::sma::*;
+    use crate::indicator::storage::*;
+    use crate::vec::*;
+
+    fn ts(s: &str) -> i64 {
+        s.parse::<DateTime<Utc>>().unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_sma_over_weekend() {
+        let offset = SessionTime::<M1, Fx>::new(ts("2019-01-04T21:57:00Z"));
+        let source = VecIndicator::new(offset, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let sma = sma(source, 3);
+
+        let t = SessionTime::new(ts("2019-01-06T22:01:00Z"));
+        assert_eq!(sma.value(t), Fixed(InRange(4.0)));
+    }
+
+    #[test]
+    fn test_to_session_time() {
+        let fri = Time::<M1>::new(ts("2019-01-04T21:59:00Z"));
+        let sun = Time::<M1>::new(ts("2019-01-06T22:00:00Z"));
+        let mut storage = Storage::new(fri);
+        storage.add(fri, 1.0);
+        storage.add(sun, 2.0);
+        let session = ToSessionTime::<Fx, _>::new(storage);
+
+        let t = SessionTime::new(fri.timestamp());
+        assert_eq!(session.value(t), Fixed(InRange(Some(1.0))));
+        assert_eq!(session.value(t + 1), Fixed(InRange(Some(2.0))));
+    }
+
+    #[test]
+    fn test_gate_by_session() {
+        let offset = Time::<H1>::new(ts("2019-07-01T05:00:00Z"));
+        let signal = VecIndicator::new(offset, vec![1, 2, 3, 4]);
+        let gated = signal
+            .zip(is_in_session(Session::London))
+            .map(|(s, ok)| if ok { Some(s) } else { None });
+
+        let result = (0..4).map(|i| gated.value(offset + i)).collect::<Vec<_>>();
+        let expect = vec![
+            Fixed(InRange(None)),
+            Fixed(InRange(None)),
+            Fixed(InRange(Some(3))),
+            Fixed(InRange(Some(4))),
+        ];
+        assert_eq!(result, expect);
+        assert_eq!(
+            session_of().value(offset + 2),
+            Fixed(InRange(Some(Session::London)))
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use super::*;
+use crate::indicator::rolling::*;
+
+type Func<I> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<f64>;
+
+pub struct StdDev<I>(Rolling<I, Func<I>>)
+where
+    I: Indicator;
+
+impl<I> StdDev<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    pub fn new(source: I, period: usize) -> Self {
+        StdDev(source.rolling(period, |w| w.variance().map2(f64::sqrt)))
+    }
+}
+
+impl<I> Indicator for StdDev<I>
+where
+    I: Indicator<Val = f64>,
+{
+    type Seq = I::Seq;
+    type Val = f64;
+}
+
+impl<I> FuncIndicator for StdDev<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    #[inline]
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.0.value(seq)
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    type IPtr<S, V> = Ptr<S, V, StdDev<FuncIndicatorPtr<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                source: *mut FuncIndicatorPtr<$s, $v>,
+                period: c_int,
+            ) -> IPtr<$s, $v> {
+                let source = (*source).clone();
+                let ptr = StdDev::new(source, period as usize).into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, stddev_new_time_f64);
+    define_new!(TickId, i64, f64, f64, stddev_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, stddev_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, stddev_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+    use MaybeFixed::*;
+    use MaybeInRange::*;
+
+    #[test]
+    fn test_stddev() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 2.0, 3.0, 5.0, 5.0];
+        let expect = vec![
+            None,
+            None,
+            Some((2.0_f64 / 3.0).sqrt()),
+            Some((14.0_f64 / 9.0).sqrt()),
+            Some((8.0_f64 / 9.0).sqrt()),
+        ];
+        let source = VecIndicator::new(offset, source);
+        let stddev = StdDev::new(source, 3);
+
+        for (i, e) in expect.into_iter().enumerate() {
+            match (stddev.value(offset + i as i64), e) {
+                (Fixed(InRange(v)), Some(e)) => assert_relative_eq!(v, e, epsilon = 1e-12),
+                (Fixed(OutOfRange), None) => (),
+                (v, e) => panic!("{:?} != {:?}", v, e),
+            }
+        }
+    }
+}
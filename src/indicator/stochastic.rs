@@ -0,0 +1,178 @@
+use super::*;
+use crate::indicator::rolling::*;
+use crate::library::lru_cache::LRUCache;
+use std::cell::RefCell;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StochasticValue {
+    pub k: f64,
+    pub d: f64,
+}
+
+type Func<I> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<f64>;
+
+pub struct Stochastic<S, IH, IL, IC>
+where
+    IH: Indicator,
+    IL: Indicator,
+{
+    highest: Rolling<IH, Func<IH>>,
+    lowest: Rolling<IL, Func<IL>>,
+    close: IC,
+    d_period: usize,
+    cache: RefCell<LRUCache<S, f64>>,
+}
+
+impl<S, IH, IL, IC> Stochastic<S, IH, IL, IC>
+where
+    S: Sequence,
+    IH: FuncIndicator<Seq = S, Val = f64>,
+    IL: FuncIndicator<Seq = S, Val = f64>,
+    IC: FuncIndicator<Seq = S, Val = f64>,
+{
+    pub fn new(high: IH, low: IL, close: IC, k_period: usize, d_period: usize) -> Self {
+        assert!(d_period > 0);
+        Self {
+            highest: high.rolling(k_period, |w| w.max()),
+            lowest: low.rolling(k_period, |w| w.min()),
+            close: close,
+            d_period: d_period,
+            cache: RefCell::new(LRUCache::new(d_period)),
+        }
+    }
+
+    fn percent_k(&self, seq: S) -> MaybeValue<f64> {
+        if let Some(k) = self.cache.borrow_mut().get(&seq) {
+            return Fixed(InRange(*k));
+        }
+        let highest = try_value!(self.highest.value(seq));
+        let lowest = try_value!(self.lowest.value(seq));
+        let close = try_value!(self.close.value(seq));
+        let k = if highest == lowest {
+            50.0
+        } else {
+            100.0 * (close - lowest) / (highest - lowest)
+        };
+        self.cache.borrow_mut().insert(seq, k);
+        Fixed(InRange(k))
+    }
+}
+
+impl<S, IH, IL, IC> Indicator for Stochastic<S, IH, IL, IC>
+where
+    S: Sequence,
+    IH: Indicator<Seq = S, Val = f64>,
+    IL: Indicator<Seq = S, Val = f64>,
+    IC: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = StochasticValue;
+}
+
+impl<S, IH, IL, IC> FuncIndicator for Stochastic<S, IH, IL, IC>
+where
+    S: Sequence,
+    IH: FuncIndicator<Seq = S, Val = f64>,
+    IL: FuncIndicator<Seq = S, Val = f64>,
+    IC: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let mut sum = 0.0;
+        let mut k = 0.0;
+        for i in (0..self.d_period).rev() {
+            k = try_value!(self.percent_k(seq - i as i64));
+            sum += k;
+        }
+        Fixed(InRange(StochasticValue {
+            k: k,
+            d: sum / self.d_period as f64,
+        }))
+    }
+}
+
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    pub struct CStochasticValue {
+        k: f64,
+        d: f64,
+    }
+
+    impl From<StochasticValue> for CStochasticValue {
+        fn from(v: StochasticValue) -> Self {
+            Self { k: v.k, d: v.d }
+        }
+    }
+
+    type I<S, V> = FuncIndicatorPtr<S, V>;
+    type IPtr<S, V> = Ptr<S, StochasticValue, Stochastic<S, I<S, V>, I<S, V>, I<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                high: *mut FuncIndicatorPtr<$s, $v>,
+                low: *mut FuncIndicatorPtr<$s, $v>,
+                close: *mut FuncIndicatorPtr<$s, $v>,
+                k_period: c_int,
+                d_period: c_int,
+            ) -> IPtr<$s, $v> {
+                let high = (*high).clone();
+                let low = (*low).clone();
+                let close = (*close).clone();
+                let ptr = Stochastic::new(high, low, close, k_period as usize, d_period as usize)
+                    .into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, stochastic_new_time_f64);
+    define_new!(TickId, i64, f64, f64, stochastic_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, stochastic_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, stochastic_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+
+    #[test]
+    fn test_stochastic() {
+        let offset = Time::<S5>::new(0);
+        let close = vec![1.0, 3.0, 2.0, 5.0, 4.0];
+        let high = close.iter().map(|c| c + 1.0).collect();
+        let low = close.iter().map(|c| c - 1.0).collect();
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(StochasticValue { k: 80.0, d: 65.0 })),
+            Fixed(InRange(StochasticValue { k: 60.0, d: 70.0 })),
+        ];
+        let stoch = Stochastic::new(
+            VecIndicator::new(offset, high),
+            VecIndicator::new(offset, low),
+            VecIndicator::new(offset, close),
+            3,
+            2,
+        );
+
+        let result = (0..5).map(|i| stoch.value(offset + i)).collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+}
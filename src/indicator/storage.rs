@@ -85,6 +85,8 @@ where
 #[cfg(feature = "ffi")]
 mod hash_ffi {
     use super::*;
+    use crate::candle::ffi::*;
+    use crate::candle::*;
     use crate::granularity::ffi::*;
     use crate::indicator::ffi::*;
     use crate::indicator::*;
@@ -141,12 +143,37 @@ mod hash_ffi {
 
     define_new!(GTime<Var>, CTime, f64, f64, storage_new_time_f64);
     define_new!(TransactionId, i64, f64, f64, storage_new_tid_f64);
+    define_new!(GTime<Var>, CTime, Candle, CCandle, storage_new_time_candle);
+    define_new!(
+        GTime<Var>,
+        CTime,
+        BidAskCandle,
+        CBidAskCandle,
+        storage_new_time_bid_ask_candle
+    );
 
     define_destroy!(IPtr<GTime<Var>, f64>, storage_destroy_time_f64);
     define_destroy!(IPtr<TransactionId, f64>, storage_destroy_tid_f64);
+    define_destroy!(IPtr<GTime<Var>, Candle>, storage_destroy_time_candle);
+    define_destroy!(
+        IPtr<GTime<Var>, BidAskCandle>,
+        storage_destroy_time_bid_ask_candle
+    );
 
     define_add!(IPtr<GTime<Var>, f64>, CTime, f64, storage_add_time_f64);
     define_add!(IPtr<TransactionId, f64>, i64, f64, storage_add_tid_f64);
+    define_add!(
+        IPtr<GTime<Var>, Candle>,
+        CTime,
+        CCandle,
+        storage_add_time_candle
+    );
+    define_add!(
+        IPtr<GTime<Var>, BidAskCandle>,
+        CTime,
+        CBidAskCandle,
+        storage_add_time_bid_ask_candle
+    );
 }
 
 #[cfg(test)]
@@ -1,6 +1,10 @@
 use super::*;
+use crate::candle::*;
+use crate::granularity::*;
 use chrono::prelude::*;
 use log::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use MaybeFixed::*;
 use MaybeInRange::*;
 
@@ -40,6 +44,119 @@ where
     }
 }
 
+pub struct TickToCandle<G, IP, IT> {
+    price: IP,
+    time: IT,
+    capacity: usize,
+    state: RefCell<TickToCandleState<G>>,
+}
+
+struct TickToCandleState<G> {
+    next: TickId,
+    begin: Option<Time<G>>,
+    current: Option<(Time<G>, Candle)>,
+    candles: HashMap<Time<G>, Candle>,
+}
+
+impl<G, IP, IT> TickToCandle<G, IP, IT>
+where
+    G: StaticGranularity,
+{
+    // 確定した足は最新の足から capacity 本前までを保持する
+    pub fn new(price: IP, time: IT, offset: TickId, capacity: usize) -> Self {
+        Self {
+            price: price,
+            time: time,
+            capacity: capacity,
+            state: RefCell::new(TickToCandleState {
+                next: offset,
+                begin: None,
+                current: None,
+                candles: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl<T, G, IP, IT> TickToCandle<G, IP, IT>
+where
+    T: Into<DateTime<Utc>>,
+    G: StaticGranularity,
+    IP: FuncIndicator<Seq = TickId, Val = f64>,
+    IT: FuncIndicator<Seq = TickId, Val = T>,
+{
+    // 届いている tick を全部足に反映する
+    fn update(&self) {
+        let mut state = self.state.borrow_mut();
+        loop {
+            let seq = state.next;
+            let (price, time) = match (self.price.value(seq), self.time.value(seq)) {
+                (Fixed(InRange(p)), Fixed(InRange(t))) => (p, t.into()),
+                _ => break,
+            };
+            let bar = Time::<G>::truncate(time.timestamp());
+            state.next = seq + 1;
+            // 確定済みの足を書き換えないように遅れて届いた tick は捨てる
+            if state.current.map_or(false, |(b, _)| bar < b) {
+                warn!("drop late tick {:?} at {}", seq, time);
+                continue;
+            }
+            state.current = match state.current.take() {
+                Some((b, c)) if b == bar => Some((
+                    b,
+                    Candle {
+                        high: c.high.max(price),
+                        low: c.low.min(price),
+                        close: price,
+                        volume: c.volume + 1,
+                        ..c
+                    },
+                )),
+                prev => {
+                    if let Some((b, c)) = prev {
+                        state.candles.insert(b, c);
+                    }
+                    let oldest = bar - self.capacity as i64;
+                    state.candles.retain(|t, _| oldest <= *t);
+                    state.begin = Some(state.begin.map_or(bar, |b| b.max(oldest)));
+                    Some((bar, Candle::new(price, price, price, price, 1)))
+                }
+            };
+        }
+    }
+}
+
+impl<T, G, IP, IT> Indicator for TickToCandle<G, IP, IT>
+where
+    T: Into<DateTime<Utc>>,
+    G: StaticGranularity,
+    IP: Indicator<Seq = TickId, Val = f64>,
+    IT: Indicator<Seq = TickId, Val = T>,
+{
+    type Seq = Time<G>;
+    type Val = Option<Candle>;
+}
+
+impl<T, G, IP, IT> FuncIndicator for TickToCandle<G, IP, IT>
+where
+    T: Into<DateTime<Utc>>,
+    G: StaticGranularity,
+    IP: FuncIndicator<Seq = TickId, Val = f64>,
+    IT: FuncIndicator<Seq = TickId, Val = T>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.update();
+        let state = self.state.borrow();
+        match (state.begin, state.current) {
+            (None, _) => NotFixed,
+            (Some(begin), _) if seq < begin => Fixed(OutOfRange),
+            // 次の足の tick が来るまでは確定しない
+            (_, Some((current, _))) if current <= seq => NotFixed,
+            _ => Fixed(InRange(state.candles.get(&seq).cloned())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,6 +196,52 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(result, expect);
     }
+
+    #[test]
+    fn test_tick_to_candle() {
+        let offset = TickId(0);
+        let price = VecIndicator::new(offset, vec![1.0, 3.0, 2.0, 4.0]).into_sync_ptr();
+        let time = VecIndicator::new(
+            offset,
+            vec![
+                Time::<S5>::new(0),
+                Time::<S5>::new(5),
+                Time::<S5>::new(55),
+                Time::<S5>::new(120),
+            ],
+        )
+        .into_sync_ptr();
+        let candle = TickToCandle::new(price.clone(), time.clone(), offset, 3);
+
+        let m1 = Time::<M1>::new(0);
+        assert_eq!(
+            candle.value(m1),
+            Fixed(InRange(Some(Candle::new(1.0, 3.0, 1.0, 2.0, 3))))
+        );
+        assert_eq!(candle.value(m1 + 1), Fixed(InRange(None)));
+        assert_eq!(candle.value(m1 + 2), NotFixed);
+        assert_eq!(candle.value(m1 - 1), Fixed(OutOfRange));
+
+        // 遅れて届いた tick は確定済みの足に反映しない
+        price.borrow_mut().add(9.0);
+        time.borrow_mut().add(Time::<S5>::new(10));
+        price.borrow_mut().add(5.0);
+        time.borrow_mut().add(Time::<S5>::new(180));
+        assert_eq!(
+            candle.value(m1 + 2),
+            Fixed(InRange(Some(Candle::new(4.0, 4.0, 4.0, 4.0, 1))))
+        );
+        assert_eq!(
+            candle.value(m1),
+            Fixed(InRange(Some(Candle::new(1.0, 3.0, 1.0, 2.0, 3))))
+        );
+
+        // capacity 本より古い足は捨てる
+        price.borrow_mut().add(6.0);
+        time.borrow_mut().add(Time::<S5>::new(240));
+        assert_eq!(candle.value(m1 + 1), Fixed(InRange(None)));
+        assert_eq!(candle.value(m1), Fixed(OutOfRange));
+    }
 }
 
 #[cfg(feature = "ffi")]
@@ -126,25 +289,6 @@ mod ffi {
     define_destroy!(IPtr<GTime<Var>, f64>, tick_destroy_tick_id_f64);
 }
 
-// #[derive(Clone, Debug)]
-// pub struct Candle {
-//     open: f64,
-//     high: f64,
-//     low: f64,
-//     close: f64,
-// }
-
-// impl Candle {
-//     pub fn new(open: f64, high: f64, low: f64, close: f64) -> Self {
-//         Self {
-//             open: open,
-//             high: high,
-//             low: low,
-//             close: close,
-//         }
-//     }
-// }
-
 // pub struct IntoTick<IC, IV> {
 //     candle: IC,
 //     volume: IV,
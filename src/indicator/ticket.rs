@@ -0,0 +1,108 @@
+use super::*;
+use crate::library::lru_cache::LRUCache;
+use crate::ticket::*;
+use crate::transaction::*;
+use log::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+// 各 transaction を反映した後の TicketBook
+pub struct TicketBookHistories<I> {
+    source: I,
+    mode: NettingMode,
+    offset: TransactionId,
+    state: RefCell<(TransactionId, TicketBook)>,
+    cache: RefCell<LRUCache<TransactionId, TicketBook>>,
+}
+
+impl<I> TicketBookHistories<I> {
+    pub fn new(source: I, mode: NettingMode, offset: TransactionId, capacity: usize) -> Self {
+        Self {
+            source: source,
+            mode: mode,
+            offset: offset,
+            state: RefCell::new((offset, TicketBook::new(mode))),
+            cache: RefCell::new(LRUCache::new(capacity)),
+        }
+    }
+}
+
+impl<I> Indicator for TicketBookHistories<I>
+where
+    I: Indicator<Seq = TransactionId>,
+{
+    type Seq = I::Seq;
+    type Val = TicketBook;
+}
+
+impl<I> FuncIndicator for TicketBookHistories<I>
+where
+    I: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        if seq < self.offset {
+            return Fixed(OutOfRange);
+        }
+        if let Some(book) = self.cache.borrow_mut().get(&seq) {
+            return Fixed(InRange(book.clone()));
+        }
+        let (mut next, mut book) = self.state.borrow().clone();
+        // cache から落ちた過去の値は最初から積み直す
+        let replay = seq < next;
+        if replay {
+            next = self.offset;
+            book = TicketBook::new(self.mode);
+        }
+        while next <= seq {
+            let transaction = try_value!(self.source.value(next));
+            // 不正な transaction は無視して処理を続ける
+            if let Err(e) = book.try_apply_transaction(transaction) {
+                warn!("{}", e);
+            }
+            self.cache.borrow_mut().insert(next, book.clone());
+            next = next + 1;
+            if !replay {
+                *self.state.borrow_mut() = (next, book.clone());
+            }
+        }
+        Fixed(InRange(book))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::transaction::builder::*;
+    use crate::vec::*;
+    use LongOrShort::*;
+
+    #[test]
+    fn test_ticket_book_histories() {
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+        let source = VecIndicator::new(
+            offset,
+            vec![
+                open(offset + 0, time, TicketId(1), 100, 1.0, Long),
+                open(offset + 1, time, TicketId(2), 100, 2.0, Long),
+                // 存在しない ticket の決済は無視される
+                close(offset + 2, offset + 0, time, TicketId(9), 100, 1.5),
+                close(offset + 3, offset + 0, time, TicketId(1), 100, 1.5),
+            ],
+        );
+        let expect = vec![100, 200, 200, 100];
+
+        let books = TicketBookHistories::new(source, NettingMode::Hedging, offset, 2);
+        let result = (0..4)
+            .map(|i| books.value(offset + i).unwrap().unwrap().net_unit())
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+
+        // cache から落ちた値も取得できる
+        let book = books.value(offset + 0).unwrap().unwrap();
+        assert_eq!(book.average_price(Long), Some(1.0));
+        assert_eq!(books.value(offset + 4), NotFixed);
+        assert_eq!(books.value(offset - 1), Fixed(OutOfRange));
+    }
+}
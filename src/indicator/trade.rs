@@ -1,5 +1,7 @@
 use super::*;
+use crate::ticket::*;
 use crate::transaction::*;
+use crate::Error;
 use chrono::prelude::*;
 use MaybeFixed::*;
 use MaybeInRange::*;
@@ -17,6 +19,19 @@ pub struct Trade {
     pub close_time: DateTime<Utc>,
     pub open_price: f64,
     pub close_price: f64,
+    // account 通貨建ての手数料。建てたときの手数料は決済した unit の割合で割り振る
+    pub commission: f64,
+}
+
+impl Trade {
+    // quote 通貨建ての損益
+    pub fn profit_loss(&self) -> f64 {
+        let distance = match self.long_or_short {
+            LongOrShort::Long => self.close_price - self.open_price,
+            LongOrShort::Short => self.open_price - self.close_price,
+        };
+        distance * self.unit as f64
+    }
 }
 
 pub struct TradeHistories<I> {
@@ -37,30 +52,86 @@ where
     type Val = Option<Trade>;
 }
 
-impl<I> FuncIndicator for TradeHistories<I>
+impl<I> TradeHistories<I>
 where
     I: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
 {
-    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+    // open_id から close までの transaction を辿って、決済時点の ticket とその建玉の手数料を組み立てる
+    // 追加建ては建値を平均し、一部決済は残りの unit と手数料を減らす
+    fn open_ticket(
+        &self,
+        close: &CloseOrderFillTransaction,
+    ) -> MaybeValue<Result<(SimpleTicket, f64), Error>> {
+        let (mut ticket, mut commission) = match try_value!(self.source.value(close.open_id)) {
+            SimpleTransaction::OpenOrderFill(open) if open.ticket.id == close.ticket_id => {
+                (open.ticket, open.commission)
+            }
+            _ => return Fixed(InRange(Err(Error::InvalidTransaction(close.id)))),
+        };
+        let mut seq = close.open_id + 1;
+        while seq < close.id {
+            match try_value!(self.source.value(seq)) {
+                SimpleTransaction::OpenOrderFill(t) if t.ticket.id == ticket.id => {
+                    ticket.scale_in(t.ticket.unit, t.ticket.price);
+                    commission += t.commission;
+                }
+                SimpleTransaction::CloseOrderFill(t) if t.ticket_id == ticket.id => {
+                    let unit = ticket.unit.saturating_sub(t.unit);
+                    commission = share(commission, unit, ticket.unit);
+                    ticket.unit = unit;
+                }
+                _ => (),
+            }
+            seq = seq + 1;
+        }
+        if close.unit == 0 || ticket.unit < close.unit {
+            return Fixed(InRange(Err(Error::InvalidTransaction(close.id))));
+        }
+        Fixed(InRange(Ok((ticket, commission))))
+    }
+
+    // close の open_id が対応する OpenOrderFill を指していなければ close の id を返す
+    pub fn try_value(&self, seq: TransactionId) -> MaybeValue<Result<Option<Trade>, Error>> {
         let v = match try_value!(self.source.value(seq)) {
             SimpleTransaction::CloseOrderFill(close) => {
-                match try_value!(self.source.value(close.open_id)) {
-                    SimpleTransaction::OpenOrderFill(open) => Some(Trade {
+                match try_value!(self.open_ticket(&close)) {
+                    Ok((ticket, commission)) => Ok(Some(Trade {
                         unit: close.unit,
-                        long_or_short: open.ticket.long_or_short,
-                        open_time: open.time,
+                        long_or_short: ticket.long_or_short,
+                        open_time: ticket.open_time,
                         close_time: close.time,
-                        open_price: open.ticket.price,
+                        open_price: ticket.price,
                         close_price: close.price,
-                    }),
-                    _ => panic!("invalid transaction"),
+                        commission: share(commission, close.unit, ticket.unit) + close.commission,
+                    })),
+                    Err(e) => Err(e),
                 }
             }
-            _ => None,
+            _ => Ok(None),
         };
         Fixed(InRange(v))
     }
 }
+
+fn share(commission: f64, unit: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        commission * unit as f64 / total as f64
+    }
+}
+
+impl<I> FuncIndicator for TradeHistories<I>
+where
+    I: FuncIndicator<Seq = TransactionId, Val = SimpleTransaction>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        match try_value!(self.try_value(seq)) {
+            Ok(v) => Fixed(InRange(v)),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
 // impl<S, T, I> Indicator<S, Trade<T>> for TradeHistories<S, I> where S: Sequence {}
 
 // impl<S, T, I> FuncIndicator<S, Trade<T>> for TradeHistories<S, I>
@@ -91,7 +162,7 @@ where
 mod tests {
     use super::*;
     use crate::granularity::*;
-    use crate::ticket::*;
+    use crate::transaction::builder::*;
     use crate::vec::*;
     use LongOrShort::*;
 
@@ -102,44 +173,10 @@ mod tests {
         let source = VecIndicator::new(
             offset,
             vec![
-                SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                    id: offset + 0,
-                    time: (time + 0).into(),
-                    ticket: SimpleTicket {
-                        id: TicketId(3),
-                        open_time: (time + 0).into(),
-                        unit: 100,
-                        price: 1.234,
-                        long_or_short: Long,
-                    },
-                }),
-                SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                    id: offset + 1,
-                    time: (time + 3).into(),
-                    ticket: SimpleTicket {
-                        id: TicketId(4),
-                        open_time: (time + 3).into(),
-                        unit: 100,
-                        price: 1.4,
-                        long_or_short: Short,
-                    },
-                }),
-                SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                    id: offset + 2,
-                    open_id: offset + 0,
-                    time: (time + 5).into(),
-                    ticket_id: TicketId(3),
-                    unit: 100,
-                    price: 1.5,
-                }),
-                SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                    id: offset + 3,
-                    open_id: offset + 1,
-                    time: (time + 9).into(),
-                    ticket_id: TicketId(4),
-                    unit: 100,
-                    price: 1.1,
-                }),
+                open(offset + 0, time + 0, TicketId(3), 100, 1.234, Long),
+                open(offset + 1, time + 3, TicketId(4), 100, 1.4, Short),
+                close(offset + 2, offset + 0, time + 5, TicketId(3), 100, 1.5),
+                close(offset + 3, offset + 1, time + 9, TicketId(4), 100, 1.1),
             ],
         );
 
@@ -155,6 +192,7 @@ mod tests {
                 close_time: (time + 5).into(),
                 open_price: 1.234,
                 close_price: 1.5,
+                commission: 0.0,
             })))
         );
         assert_eq!(
@@ -166,7 +204,69 @@ mod tests {
                 close_time: (time + 9).into(),
                 open_price: 1.4,
                 close_price: 1.1,
+                commission: 0.0,
             })))
         );
     }
+
+    #[test]
+    fn test_trade_invalid_open_id() {
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+        let source = VecIndicator::new(
+            offset,
+            vec![
+                other(offset + 0, time + 0),
+                close(offset + 1, offset + 0, time + 5, TicketId(3), 100, 1.5),
+            ],
+        );
+
+        let trade = TradeHistories::new(source);
+        assert_eq!(trade.try_value(offset + 0), Fixed(InRange(Ok(None))));
+        assert_eq!(
+            trade.try_value(offset + 1),
+            Fixed(InRange(Err(Error::InvalidTransaction(offset + 1))))
+        );
+    }
+
+    #[test]
+    fn test_trade_partial_close() {
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+        // 手数料は 1 unit あたり 0.01
+        let open = |id, unit: usize, price| {
+            let t = open(id, time + 0, TicketId(3), unit, price, Long);
+            with_commission(t, unit as f64 * 0.01)
+        };
+        let close = |id, unit: usize, price| {
+            let t = close(id, offset + 0, time + 5, TicketId(3), unit, price);
+            with_commission(t, unit as f64 * 0.01)
+        };
+        let source = VecIndicator::new(
+            offset,
+            vec![
+                open(offset + 0, 100, 1.0),
+                close(offset + 1, 40, 1.5),
+                open(offset + 2, 140, 2.0),
+                close(offset + 3, 200, 2.5),
+                close(offset + 4, 1, 2.5),
+            ],
+        );
+
+        let trade = TradeHistories::new(source);
+        let result = trade.value(offset + 1).unwrap().unwrap().unwrap();
+        assert_eq!((result.unit, result.open_price), (40, 1.0));
+        // 決済分 0.4 と建玉 100 unit の手数料 1.0 のうち 40 unit 分
+        approx::assert_relative_eq!(result.commission, 0.8);
+        // 残り 60 @ 1.0 に 140 @ 2.0 を追加して 200 @ 1.7
+        let result = trade.value(offset + 3).unwrap().unwrap().unwrap();
+        assert_eq!(result.unit, 200);
+        approx::assert_relative_eq!(result.open_price, 1.7);
+        // 決済分 2.0 と残りの建玉の手数料 0.6 + 1.4
+        approx::assert_relative_eq!(result.commission, 4.0, max_relative = 0.0000001);
+        assert_eq!(
+            trade.try_value(offset + 4),
+            Fixed(InRange(Err(Error::InvalidTransaction(offset + 4))))
+        );
+    }
 }
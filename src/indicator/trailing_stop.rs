@@ -1,96 +1,177 @@
 use super::*;
-use crate::seq::*;
 use crate::position::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
 
-pub struct TrailingStop<G, IPrice, IPos> {
+pub trait StopDistance<S> {
+    fn distance(&self, seq: S) -> MaybeValue<f64>;
+}
+
+impl<S> StopDistance<S> for f64 {
+    fn distance(&self, _seq: S) -> MaybeValue<f64> {
+        Fixed(InRange(*self))
+    }
+}
+
+pub fn pips(pips: f64, pip_size: f64) -> f64 {
+    pips * pip_size
+}
+
+pub struct AtrMultiple<I> {
+    atr: I,
+    multiplier: f64,
+}
+
+impl<I> AtrMultiple<I> {
+    pub fn new(atr: I, multiplier: f64) -> Self {
+        Self {
+            atr: atr,
+            multiplier: multiplier,
+        }
+    }
+}
+
+impl<S, I> StopDistance<S> for AtrMultiple<I>
+where
+    I: FuncIndicator<Seq = S, Val = f64>,
+{
+    fn distance(&self, seq: S) -> MaybeValue<f64> {
+        let atr = try_value!(self.atr.value(seq));
+        Fixed(InRange(atr * self.multiplier))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TrailingStopSignal {
+    Continue,
+    Stop,
+}
+
+struct TrailingStopState<S> {
+    seq: S,
+    position: SimplePosition,
+    // 建ててからの最高値 (Long) または最安値 (Short)
+    extreme: f64,
+}
+
+pub struct TrailingStop<S, IPrice, IPos, D> {
     price: IPrice,
     position: IPos,
-    stop_level: f64,
-    phantom: std::marker::PhantomData<G>,
+    distance: D,
+    state: RefCell<Option<TrailingStopState<S>>>,
 }
 
-impl<G, IPrice, IPos> TrailingStop<G, IPrice, IPos>
+impl<S, IPrice, IPos, D> TrailingStop<S, IPrice, IPos, D>
 where
-    IPrice: Indicator<G, f64>,
-    IPos: Indicator<G, SimplePosition>,
+    S: Sequence,
 {
-    pub fn new(price: IPrice, position: IPos, stop_level: f64) -> Self {
+    pub fn new(price: IPrice, position: IPos, distance: D) -> Self {
         Self {
             price: price,
             position: position,
-            stop_level: stop_level,
-            phantom: std::marker::PhantomData,
+            distance: distance,
+            state: RefCell::new(None),
         }
     }
 }
 
-impl<G, IPrice, IPos> Indicator<G, TrailingStopSignal> for TrailingStop<G, IPrice, IPos>
+impl<S, P, IPrice, IPos, D> TrailingStop<S, IPrice, IPos, D>
 where
-    G: Granularity + Copy + Ord,
-    IPrice: Indicator<G, f64>,
-    IPos: Indicator<G, SimplePosition>,
+    S: Sequence,
+    P: Into<SimplePosition>,
+    IPrice: FuncIndicator<Seq = S, Val = f64>,
+    IPos: FuncIndicator<Seq = S, Val = P>,
 {
-    fn value(&self, time: Time<G>) -> Option<TrailingStopSignal> {
-        use SimplePosition::*;
-        use TrailingStopSignal::*;
-        let pos = self.position.value(time)?;
-        let price = self.price.value(time)?;
-        // TODO: performance tuning
-        let order_begin = {
-            let mut i = time - 1;
-            while self.position.value(i) == Some(pos.clone()) {
-                i = i - 1;
-            }
-            i + 1
-        };
-        let prices_in_order = order_begin
-            .range_to_end(time + 1)
-            .filter_map(|i| self.price.value(i));
-
-        let signal = match pos {
-            Nothing => Continue,
-            Long => {
-                let max = prices_in_order.fold(-1.0 / 0.0, f64::max);
-                if max - price > self.stop_level {
-                    Stop
-                } else {
-                    Continue
+    // 同じ position が続いている間を遡って extreme を求める
+    fn scan_extreme(&self, seq: S, position: SimplePosition) -> MaybeValue<f64> {
+        let mut extreme = try_value!(self.price.value(seq));
+        let mut i = seq - 1;
+        loop {
+            match self.position.value(i) {
+                Fixed(InRange(p)) => {
+                    if p.into() != position {
+                        break;
+                    }
                 }
+                Fixed(OutOfRange) => break,
+                NotFixed => return NotFixed,
             }
-            Short => {
-                let min = prices_in_order.fold(1.0 / 0.0, f64::min);
-                if price - min > self.stop_level {
-                    Stop
-                } else {
-                    Continue
-                }
+            extreme = fold_extreme(position, extreme, try_value!(self.price.value(i)));
+            i = i - 1;
+        }
+        Fixed(InRange(extreme))
+    }
+
+    fn extreme(&self, seq: S, position: SimplePosition, price: f64) -> MaybeValue<f64> {
+        let cached = match &*self.state.borrow() {
+            Some(s) if s.position == position && s.seq == seq => Some(s.extreme),
+            Some(s) if s.position == position && s.seq + 1 == seq => {
+                Some(fold_extreme(position, s.extreme, price))
             }
+            _ => None,
         };
-        Some(signal)
+        let extreme = match cached {
+            Some(e) => e,
+            None => try_value!(self.scan_extreme(seq, position)),
+        };
+        *self.state.borrow_mut() = Some(TrailingStopState {
+            seq: seq,
+            position: position,
+            extreme: extreme,
+        });
+        Fixed(InRange(extreme))
     }
-    fn granularity(&self) -> G {
-        self.price.granularity()
+}
+
+fn fold_extreme(position: SimplePosition, extreme: f64, price: f64) -> f64 {
+    match position {
+        SimplePosition::Short => extreme.min(price),
+        _ => extreme.max(price),
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub enum TrailingStopSignal {
-    Continue,
-    Stop,
+impl<S, IPrice, IPos, D> Indicator for TrailingStop<S, IPrice, IPos, D>
+where
+    S: Sequence,
+    IPrice: Indicator<Seq = S, Val = f64>,
+{
+    type Seq = S;
+    type Val = TrailingStopSignal;
 }
 
+impl<S, P, IPrice, IPos, D> FuncIndicator for TrailingStop<S, IPrice, IPos, D>
+where
+    S: Sequence,
+    P: Into<SimplePosition>,
+    IPrice: FuncIndicator<Seq = S, Val = f64>,
+    IPos: FuncIndicator<Seq = S, Val = P>,
+    D: StopDistance<S>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        use SimplePosition::*;
+        use TrailingStopSignal::*;
+        let position = try_value!(self.position.value(seq)).into();
+        let price = try_value!(self.price.value(seq));
+        if position == Nothing {
+            return Fixed(InRange(Continue));
+        }
+        let extreme = try_value!(self.extreme(seq, position, price));
+        let distance = try_value!(self.distance.distance(seq));
+        let signal = match position {
+            Long if extreme - price > distance => Stop,
+            Short if price - extreme > distance => Stop,
+            _ => Continue,
+        };
+        Fixed(InRange(signal))
+    }
+}
+
+#[cfg(feature = "ffi")]
 pub mod ffi {
     use super::*;
+    use crate::granularity::ffi::*;
     use crate::indicator::ffi::*;
-    use crate::indicator::*;
-    use crate::position::ffi::*;
-    use crate::position::*;
     use crate::time::ffi::*;
-    use std::cell::RefCell;
-    use std::mem::drop;
-    use std::os::raw::*;
-    use std::ptr;
-    use std::rc::Rc;
 
     #[repr(C)]
     #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -113,52 +194,89 @@ pub mod ffi {
         }
     }
 
-    #[repr(C)]
-    pub struct Ptr {
-        b_ptr: *mut Rc<
-            RefCell<TrailingStop<VarGranularity, IndicatorPtr<f64>, IndicatorPtr<SimplePosition>>>,
-        >,
-        t_ptr: *mut IndicatorPtr<TrailingStopSignal>,
+    type IPtr<S, D> = Ptr<
+        S,
+        TrailingStopSignal,
+        TrailingStop<S, FuncIndicatorPtr<S, f64>, FuncIndicatorPtr<S, SimplePosition>, D>,
+    >;
+    type AtrPtr<S> = AtrMultiple<FuncIndicatorPtr<S, f64>>;
+
+    pub unsafe fn new<S, D>(
+        price: *mut FuncIndicatorPtr<S, f64>,
+        position: *mut FuncIndicatorPtr<S, SimplePosition>,
+        distance: D,
+    ) -> IPtr<S, D>
+    where
+        S: Sequence + 'static,
+        D: StopDistance<S> + 'static,
+    {
+        let price = (*price).clone();
+        let position = (*position).clone();
+        let ptr = TrailingStop::new(price, position, distance).into_sync_ptr();
+        Ptr {
+            b_ptr: Box::into_raw(Box::new(ptr.clone())),
+            f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+        }
     }
 
-    macro_rules! define_trailingstop_methods {
-        ($new:ident, $destroy:ident) => {
+    macro_rules! define_new {
+        ($s:ty, $new:ident, $new_atr:ident) => {
             #[no_mangle]
             pub unsafe extern "C" fn $new(
-                price: *mut IndicatorPtr<f64>,
-                position: *mut IndicatorPtr<SimplePosition>,
-                stop_level: f64,
-            ) -> Ptr {
-                let price = (*price).clone();
-                let position = (*position).clone();
-                let ptr = Rc::new(RefCell::new(TrailingStop::new(price, position, stop_level)));
-                Ptr {
-                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
-                    t_ptr: Box::into_raw(Box::new(IndicatorPtr(ptr))),
-                }
+                price: *mut FuncIndicatorPtr<$s, f64>,
+                position: *mut FuncIndicatorPtr<$s, SimplePosition>,
+                distance: f64,
+            ) -> IPtr<$s, f64> {
+                new(price, position, distance)
             }
 
             #[no_mangle]
-            pub unsafe extern "C" fn $destroy(ptr: Ptr) {
-                destroy(ptr.b_ptr);
-                destroy(ptr.t_ptr);
+            pub unsafe extern "C" fn $new_atr(
+                price: *mut FuncIndicatorPtr<$s, f64>,
+                position: *mut FuncIndicatorPtr<$s, SimplePosition>,
+                atr: *mut FuncIndicatorPtr<$s, f64>,
+                multiplier: f64,
+            ) -> IPtr<$s, AtrPtr<$s>> {
+                let atr = (*atr).clone();
+                new(price, position, AtrMultiple::new(atr, multiplier))
             }
         };
     }
 
-    define_trailingstop_methods!(trailingstop_new, trailingstop_destroy);
+    define_new!(
+        GTime<Var>,
+        trailing_stop_new_time,
+        trailing_stop_new_atr_time
+    );
+    define_new!(
+        TickId,
+        trailing_stop_new_tick_id,
+        trailing_stop_new_atr_tick_id
+    );
+
+    define_destroy!(IPtr<GTime<Var>, f64>, trailing_stop_destroy_time);
+    define_destroy!(
+        IPtr<GTime<Var>, AtrPtr<GTime<Var>>>,
+        trailing_stop_destroy_atr_time
+    );
+    define_destroy!(IPtr<TickId, f64>, trailing_stop_destroy_tick_id);
+    define_destroy!(
+        IPtr<TickId, AtrPtr<TickId>>,
+        trailing_stop_destroy_atr_tick_id
+    );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::granularity::*;
     use crate::position::SimplePosition::*;
     use crate::vec::*;
     use TrailingStopSignal::*;
 
     #[test]
     fn test_1() {
-        let offset = Time::new(0, S5);
+        let offset = Time::<S5>::new(0);
         let price = vec![1.0, 2.0, -3.0, 8.0, 3.0];
         let price = VecIndicator::new(offset, price);
         let position = vec![Long; 5];
@@ -167,9 +285,46 @@ mod tests {
         let trailing_stop = TrailingStop::new(price, position, 4.0);
 
         let result = (0..5)
-            .map(|i| trailing_stop.value(offset + i).unwrap())
+            .map(|i| trailing_stop.value(offset + i).unwrap().unwrap())
             .collect::<Vec<_>>();
 
         assert_eq!(result, expect);
     }
+
+    #[test]
+    fn test_position_change() {
+        let offset = TickId(0);
+        let price = vec![1.0, 1.5, 1.2, 1.3, 1.1, 1.4, 1.0, 1.2];
+        let price = VecIndicator::new(offset, price);
+        let position = vec![Nothing, Long, Long, Short, Short, Short, Long, Long];
+        let position = VecIndicator::new(offset, position);
+        let atr = VecIndicator::new(offset, vec![0.1; 8]);
+        let expect = vec![
+            Fixed(InRange(Continue)),
+            Fixed(InRange(Continue)),
+            Fixed(InRange(Stop)),
+            Fixed(InRange(Continue)),
+            Fixed(InRange(Continue)),
+            Fixed(InRange(Stop)),
+            Fixed(InRange(Continue)),
+            Fixed(InRange(Continue)),
+        ];
+        let trailing_stop = TrailingStop::new(price, position, AtrMultiple::new(atr, 2.0));
+
+        let result = (0..8)
+            .map(|i| trailing_stop.value(offset + i))
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+
+        // 逆順に取得しても同じ結果になる
+        let result = (0..8)
+            .rev()
+            .map(|i| trailing_stop.value(offset + i))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+        approx::assert_relative_eq!(pips(20.0, 0.01), 0.2);
+    }
 }
@@ -0,0 +1,130 @@
+use super::*;
+use crate::indicator::rolling::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+type Func<I> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<f64>;
+
+pub struct WilliamsR<IH, IL, IC>
+where
+    IH: Indicator,
+    IL: Indicator,
+{
+    highest: Rolling<IH, Func<IH>>,
+    lowest: Rolling<IL, Func<IL>>,
+    close: IC,
+}
+
+impl<IH, IL, IC> WilliamsR<IH, IL, IC>
+where
+    IH: FuncIndicator<Val = f64>,
+    IL: FuncIndicator<Seq = IH::Seq, Val = f64>,
+    IC: FuncIndicator<Seq = IH::Seq, Val = f64>,
+{
+    pub fn new(high: IH, low: IL, close: IC, period: usize) -> Self {
+        Self {
+            highest: high.rolling(period, |w| w.max()),
+            lowest: low.rolling(period, |w| w.min()),
+            close: close,
+        }
+    }
+}
+
+impl<IH, IL, IC> Indicator for WilliamsR<IH, IL, IC>
+where
+    IH: Indicator<Val = f64>,
+    IL: Indicator<Seq = IH::Seq, Val = f64>,
+    IC: Indicator<Seq = IH::Seq, Val = f64>,
+{
+    type Seq = IH::Seq;
+    type Val = f64;
+}
+
+impl<IH, IL, IC> FuncIndicator for WilliamsR<IH, IL, IC>
+where
+    IH: FuncIndicator<Val = f64>,
+    IL: FuncIndicator<Seq = IH::Seq, Val = f64>,
+    IC: FuncIndicator<Seq = IH::Seq, Val = f64>,
+{
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        let highest = try_value!(self.highest.value(seq));
+        let lowest = try_value!(self.lowest.value(seq));
+        let close = try_value!(self.close.value(seq));
+        if highest == lowest {
+            Fixed(InRange(-50.0))
+        } else {
+            Fixed(InRange(-100.0 * (highest - close) / (highest - lowest)))
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    type I<S, V> = FuncIndicatorPtr<S, V>;
+    type IPtr<S, V> = Ptr<S, V, WilliamsR<I<S, V>, I<S, V>, I<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                high: *mut FuncIndicatorPtr<$s, $v>,
+                low: *mut FuncIndicatorPtr<$s, $v>,
+                close: *mut FuncIndicatorPtr<$s, $v>,
+                period: c_int,
+            ) -> IPtr<$s, $v> {
+                let high = (*high).clone();
+                let low = (*low).clone();
+                let close = (*close).clone();
+                let ptr = WilliamsR::new(high, low, close, period as usize).into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, williams_r_new_time_f64);
+    define_new!(TickId, i64, f64, f64, williams_r_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, williams_r_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, williams_r_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+
+    #[test]
+    fn test_williams_r() {
+        let offset = Time::<S5>::new(0);
+        let close = vec![1.0, 3.0, 2.0, 5.0, 4.0];
+        let high = close.iter().map(|c| c + 1.0).collect();
+        let low = close.iter().map(|c| c - 1.0).collect();
+        let expect = vec![
+            Fixed(OutOfRange),
+            Fixed(OutOfRange),
+            Fixed(InRange(-50.0)),
+            Fixed(InRange(-20.0)),
+            Fixed(InRange(-40.0)),
+        ];
+        let williams_r = WilliamsR::new(
+            VecIndicator::new(offset, high),
+            VecIndicator::new(offset, low),
+            VecIndicator::new(offset, close),
+            3,
+        );
+
+        let result = (0..5)
+            .map(|i| williams_r.value(offset + i))
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+}
@@ -0,0 +1,106 @@
+use super::*;
+use crate::indicator::rolling::*;
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+type Func<I> = fn(FixedSizeWindow<<I as Indicator>::Seq, I>) -> MaybeValue<f64>;
+
+pub struct ZScore<I>(Rolling<I, Func<I>>)
+where
+    I: Indicator;
+
+impl<I> ZScore<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    pub fn new(source: I, period: usize) -> Self {
+        ZScore(source.rolling(period, |w| {
+            let last = try_value!(w.clone().last());
+            let mean = try_value!(w.clone().mean());
+            let stddev = try_value!(w.variance()).sqrt();
+            // 値が全部同じときは 0 にしておく
+            if stddev == 0.0 {
+                Fixed(InRange(0.0))
+            } else {
+                Fixed(InRange((last - mean) / stddev))
+            }
+        }))
+    }
+}
+
+impl<I> Indicator for ZScore<I>
+where
+    I: Indicator<Val = f64>,
+{
+    type Seq = I::Seq;
+    type Val = f64;
+}
+
+impl<I> FuncIndicator for ZScore<I>
+where
+    I: FuncIndicator<Val = f64>,
+{
+    #[inline]
+    fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
+        self.0.value(seq)
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use super::*;
+    use crate::granularity::ffi::*;
+    use crate::indicator::ffi::*;
+    use crate::time::ffi::*;
+
+    type IPtr<S, V> = Ptr<S, V, ZScore<FuncIndicatorPtr<S, V>>>;
+
+    macro_rules! define_new {
+        ($s:ty, $cs:ty, $v:ty, $cv:ty, $name:ident) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(
+                source: *mut FuncIndicatorPtr<$s, $v>,
+                period: c_int,
+            ) -> IPtr<$s, $v> {
+                let source = (*source).clone();
+                let ptr = ZScore::new(source, period as usize).into_sync_ptr();
+                Ptr {
+                    b_ptr: Box::into_raw(Box::new(ptr.clone())),
+                    f_ptr: Box::into_raw(Box::new(FuncIndicatorPtr(ptr))),
+                }
+            }
+        };
+    }
+
+    define_new!(GTime<Var>, CTime, f64, f64, zscore_new_time_f64);
+    define_new!(TickId, i64, f64, f64, zscore_new_tick_id_f64);
+
+    define_destroy!(IPtr<GTime<Var>, f64>, zscore_destroy_time_f64);
+    define_destroy!(IPtr<TickId, f64>, zscore_destroy_tick_id_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_zscore() {
+        let offset = Time::<S5>::new(0);
+        let source = vec![1.0, 2.0, 3.0, 5.0, 5.0, 5.0];
+        let source = VecIndicator::new(offset, source);
+        let zscore = ZScore::new(source, 3);
+
+        assert_eq!(zscore.value(offset + 1), Fixed(OutOfRange));
+        match zscore.value(offset + 3) {
+            Fixed(InRange(v)) => {
+                let expect = (5.0 - 10.0 / 3.0) / (14.0_f64 / 9.0).sqrt();
+                assert_relative_eq!(v, expect, epsilon = 1e-12);
+            }
+            v => panic!("{:?}", v),
+        }
+        assert_eq!(zscore.value(offset + 5), Fixed(InRange(0.0)));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Error;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instrument {
+    pub base: String,
+    pub quote: String,
+    // 1 pip = 10 ^ pip_location
+    pub pip_location: i32,
+    pub min_trade_size: usize,
+}
+
+impl Instrument {
+    pub fn new(base: &str, quote: &str, pip_location: i32, min_trade_size: usize) -> Self {
+        Self {
+            base: base.to_string(),
+            quote: quote.to_string(),
+            pip_location: pip_location,
+            min_trade_size: min_trade_size,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}_{}", self.base, self.quote)
+    }
+
+    pub fn pip_size(&self) -> f64 {
+        10f64.powi(self.pip_location)
+    }
+
+    pub fn to_pips(&self, distance: f64) -> f64 {
+        distance / self.pip_size()
+    }
+
+    pub fn is_valid_unit(&self, unit: usize) -> bool {
+        unit >= self.min_trade_size
+    }
+
+    // この instrument の価格から quote 通貨 -> account 通貨の換算レートを求める
+    // どちらの通貨も account 通貨でなければ別の通貨ペアが必要なので None
+    pub fn conversion_rate(&self, account_currency: &str, price: f64) -> Option<f64> {
+        if self.quote == account_currency {
+            Some(1.0)
+        } else if self.base == account_currency {
+            Some(1.0 / price)
+        } else {
+            None
+        }
+    }
+}
+
+// "EUR_USD" 形式。JPY が quote なら pip は 0.01
+impl std::str::FromStr for Instrument {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let currencies = s.split('_').collect::<Vec<_>>();
+        match currencies.as_slice() {
+            [base, quote] if base.len() == 3 && quote.len() == 3 => {
+                let pip_location = if *quote == "JPY" { -2 } else { -4 };
+                Ok(Instrument::new(base, quote, pip_location, 1))
+            }
+            _ => Err(Error::InvalidInstrument(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_instrument() {
+        let eur_usd: Instrument = "EUR_USD".parse().unwrap();
+        assert_eq!(eur_usd.name(), "EUR_USD");
+        assert_relative_eq!(eur_usd.pip_size(), 0.0001);
+        assert_relative_eq!(eur_usd.to_pips(0.0025), 25.0, max_relative = 0.0000001);
+        assert_eq!(eur_usd.conversion_rate("USD", 1.1), Some(1.0));
+
+        let usd_jpy: Instrument = "USD_JPY".parse().unwrap();
+        assert_relative_eq!(usd_jpy.pip_size(), 0.01);
+        assert_relative_eq!(usd_jpy.conversion_rate("USD", 125.0).unwrap(), 0.008);
+        assert_eq!(usd_jpy.conversion_rate("EUR", 125.0), None);
+
+        assert_eq!(
+            "EURUSD".parse::<Instrument>(),
+            Err(Error::InvalidInstrument("EURUSD".to_string()))
+        );
+        assert!(!Instrument::new("EUR", "GBP", -4, 1000).is_valid_unit(100));
+    }
+}
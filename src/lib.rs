@@ -62,6 +62,8 @@ pub mod ffi {
 }
 
 pub mod transaction;
+pub mod calendar;
+pub mod candle;
 #[macro_use]
 pub mod indicator;
 pub mod position;
@@ -70,10 +72,50 @@ pub mod granularity;
 pub mod time;
 pub mod strategy;
 pub mod ticket;
+pub mod order;
+pub mod broker;
+pub mod account;
+pub mod instrument;
+pub mod report;
+pub mod backtest;
+pub mod data;
 pub mod signal;
 
 use indicator::*;
 
+#[derive(Clone, PartialEq, Debug)]
+pub enum Error {
+    InvalidTime(i64),
+    MarketClosed(i64),
+    InvalidTransaction(seq::TransactionId),
+    InvalidTicketId(ticket::TicketId),
+    InvalidOrderId(order::OrderId),
+    InvalidUnit(usize),
+    InsufficientMargin(f64),
+    NoQuote,
+    InvalidInstrument(String),
+    InvalidData(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::InvalidTime(t) => write!(f, "invalid time: {}", t),
+            Error::MarketClosed(t) => write!(f, "market closed: {}", t),
+            Error::InvalidTransaction(id) => write!(f, "invalid transaction: {:?}", id),
+            Error::InvalidTicketId(id) => write!(f, "invalid ticket_id: {:?}", id),
+            Error::InvalidOrderId(id) => write!(f, "invalid order_id: {:?}", id),
+            Error::InvalidUnit(u) => write!(f, "invalid unit: {}", u),
+            Error::InsufficientMargin(m) => write!(f, "insufficient margin: {}", m),
+            Error::NoQuote => write!(f, "no quote"),
+            Error::InvalidInstrument(s) => write!(f, "invalid instrument: {}", s),
+            Error::InvalidData(s) => write!(f, "invalid data: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,422 @@
+use crate::seq::*;
+use crate::ticket::*;
+use crate::transaction::order::*;
+use crate::transaction::*;
+use crate::Error;
+use chrono::prelude::*;
+use log::*;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct OrderId(pub i64);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quote {
+    pub bid: f64,
+    pub ask: f64,
+}
+
+impl Quote {
+    pub fn new(bid: f64, ask: f64) -> Self {
+        debug_assert!(bid <= ask);
+        Self { bid: bid, ask: ask }
+    }
+
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OrderType {
+    Market,
+    Limit(f64),
+    Stop(f64),
+    TrailingStop(f64),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpenOrder {
+    pub order_type: OrderType,
+    pub unit: usize,
+    pub long_or_short: LongOrShort,
+    pub on_fill: Vec<SimpleOrder>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CloseOrder {
+    pub order_type: OrderType,
+    pub ticket_id: TicketId,
+    pub unit: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Order {
+    Open(OpenOrder),
+    Close(CloseOrder),
+}
+
+impl Order {
+    pub fn market(unit: usize, long_or_short: LongOrShort) -> Self {
+        Order::Open(OpenOrder {
+            order_type: OrderType::Market,
+            unit: unit,
+            long_or_short: long_or_short,
+            on_fill: Vec::new(),
+        })
+    }
+
+    pub fn close(ticket_id: TicketId, unit: usize) -> Self {
+        Order::Close(CloseOrder {
+            order_type: OrderType::Market,
+            ticket_id: ticket_id,
+            unit: unit,
+        })
+    }
+
+    // 買いは ask、売りは bid で約定する
+    fn is_buy(&self, tickets: &TicketBook) -> Option<bool> {
+        match self {
+            Order::Open(o) => Some(o.long_or_short == LongOrShort::Long),
+            Order::Close(o) => tickets
+                .ticket(o.ticket_id)
+                .map(|t| t.long_or_short == LongOrShort::Short),
+        }
+    }
+
+    pub fn unit(&self) -> usize {
+        match self {
+            Order::Open(o) => o.unit,
+            Order::Close(o) => o.unit,
+        }
+    }
+
+    fn order_type(&self) -> OrderType {
+        match self {
+            Order::Open(o) => o.order_type,
+            Order::Close(o) => o.order_type,
+        }
+    }
+}
+
+struct PendingOrder {
+    id: OrderId,
+    order: Order,
+    // TrailingStop の現在の逆指値
+    stop: Option<f64>,
+    // この時刻までは約定判定しない
+    active_from: Option<DateTime<Utc>>,
+}
+
+// 発注された注文を bid/ask に対して約定させ、SimpleTransaction を生成する
+pub struct OrderMatcher {
+    next_order_id: OrderId,
+    next_tid: TransactionId,
+    next_ticket_id: TicketId,
+    orders: Vec<PendingOrder>,
+    tickets: TicketBook,
+}
+
+impl OrderMatcher {
+    pub fn new(tid_offset: TransactionId, ticket_id_offset: TicketId) -> Self {
+        Self {
+            next_order_id: OrderId(0),
+            next_tid: tid_offset,
+            next_ticket_id: ticket_id_offset,
+            orders: Vec::new(),
+            tickets: TicketBook::new(NettingMode::Hedging),
+        }
+    }
+
+    pub fn submit(&mut self, order: Order) -> Result<OrderId, Error> {
+        Self::validate(&order)?;
+        Ok(self.push(order, None))
+    }
+
+    // time 以降の update で初めて約定判定する
+    pub fn submit_after(&mut self, order: Order, time: DateTime<Utc>) -> Result<OrderId, Error> {
+        Self::validate(&order)?;
+        Ok(self.push(order, Some(time)))
+    }
+
+    fn validate(order: &Order) -> Result<(), Error> {
+        if order.unit() == 0 {
+            return Err(Error::InvalidUnit(order.unit()));
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, order: Order, active_from: Option<DateTime<Utc>>) -> OrderId {
+        let id = self.next_order_id;
+        self.next_order_id = OrderId(id.0 + 1);
+        self.orders.push(PendingOrder {
+            id: id,
+            order: order,
+            stop: None,
+            active_from: active_from,
+        });
+        id
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Result<(), Error> {
+        match self.orders.iter().position(|o| o.id == id) {
+            Some(i) => {
+                self.orders.remove(i);
+                Ok(())
+            }
+            None => Err(Error::InvalidOrderId(id)),
+        }
+    }
+
+    pub fn pending_orders(&self) -> Vec<(OrderId, Order)> {
+        self.orders
+            .iter()
+            .map(|o| (o.id, o.order.clone()))
+            .collect()
+    }
+
+    pub fn tickets(&self) -> &TicketBook {
+        &self.tickets
+    }
+
+    // 約定した注文の transaction を発注順に返す
+    // 約定時に付随する TP/SL 注文は次の update から判定される
+    pub fn update(&mut self, time: DateTime<Utc>, quote: Quote) -> Vec<SimpleTransaction> {
+        let mut transactions = Vec::new();
+        let mut on_fill = Vec::new();
+        let orders = std::mem::replace(&mut self.orders, Vec::new());
+        for mut pending in orders {
+            if pending.active_from.map_or(false, |t| time < t) {
+                self.orders.push(pending);
+                continue;
+            }
+            let is_buy = match pending.order.is_buy(&self.tickets) {
+                Some(is_buy) => is_buy,
+                // 決済済みの ticket に対する注文は取り消す
+                None => continue,
+            };
+            let price = if is_buy { quote.ask } else { quote.bid };
+            let is_triggered = match pending.order.order_type() {
+                OrderType::Market => true,
+                OrderType::Limit(p) => (is_buy && price <= p) || (!is_buy && p <= price),
+                OrderType::Stop(p) => (is_buy && p <= price) || (!is_buy && price <= p),
+                OrderType::TrailingStop(distance) => {
+                    let stop = match (pending.stop, is_buy) {
+                        (Some(s), true) => s.min(price + distance),
+                        (Some(s), false) => s.max(price - distance),
+                        (None, true) => price + distance,
+                        (None, false) => price - distance,
+                    };
+                    pending.stop = Some(stop);
+                    (is_buy && stop <= price) || (!is_buy && price <= stop)
+                }
+            };
+            if !is_triggered {
+                self.orders.push(pending);
+                continue;
+            }
+            let id = pending.id;
+            let ticket_id = self.next_ticket_id;
+            let mut attached = Vec::new();
+            let t = match pending.order {
+                Order::Open(o) => {
+                    let unit = o.unit;
+                    attached.extend(o.on_fill.into_iter().map(|s| {
+                        let order_type = match s {
+                            SimpleOrder::TakeProfit(s) => OrderType::Limit(s.price),
+                            SimpleOrder::StopLoss(s) => OrderType::Stop(s.price),
+                            SimpleOrder::TrailingStopLoss(s) => OrderType::TrailingStop(s.distance),
+                        };
+                        Order::Close(CloseOrder {
+                            order_type: order_type,
+                            ticket_id: ticket_id,
+                            unit: unit,
+                        })
+                    }));
+                    SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
+                        id: self.next_tid,
+                        time: time,
+                        ticket: SimpleTicket {
+                            id: ticket_id,
+                            open_time: time,
+                            unit: o.unit,
+                            price: price,
+                            long_or_short: o.long_or_short,
+                        },
+                        // 手数料は SimulatedBroker が約定後に付ける
+                        commission: 0.0,
+                    })
+                }
+                Order::Close(o) => {
+                    // 他の注文で一部決済されていれば残りだけを決済する
+                    let remain = self.tickets.ticket(o.ticket_id).unwrap().unit;
+                    SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
+                        id: self.next_tid,
+                        open_id: self.tickets.open_id(o.ticket_id).unwrap(),
+                        time: time,
+                        ticket_id: o.ticket_id,
+                        unit: o.unit.min(remain),
+                        price: price,
+                        commission: 0.0,
+                    })
+                }
+            };
+            // ticket と整合しない注文は約定させずに捨てる
+            if let Err(e) = self.tickets.try_apply_transaction(t.clone()) {
+                warn!("drop order {:?}: {}", id, e);
+                continue;
+            }
+            if let SimpleTransaction::OpenOrderFill(_) = t {
+                self.next_ticket_id = ticket_id + 1;
+            }
+            self.next_tid = self.next_tid + 1;
+            transactions.push(t);
+            on_fill.extend(attached);
+        }
+        for order in on_fill {
+            self.push(order, None);
+        }
+        transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::granularity::*;
+    use crate::indicator::balance::*;
+    use crate::indicator::trade::*;
+    use crate::indicator::*;
+    use crate::time::*;
+    use crate::transaction::builder::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+    use LongOrShort::*;
+
+    #[test]
+    fn test_market_and_pending() {
+        let time = Time::<S5>::new(0).into();
+        let mut matcher = OrderMatcher::new(TransactionId(0), TicketId(0));
+        matcher.submit(Order::market(100, Long)).unwrap();
+        let limit = matcher
+            .submit(Order::Open(OpenOrder {
+                order_type: OrderType::Limit(1.0),
+                unit: 100,
+                long_or_short: Long,
+                on_fill: Vec::new(),
+            }))
+            .unwrap();
+        matcher
+            .submit(Order::Open(OpenOrder {
+                order_type: OrderType::Stop(0.9),
+                unit: 100,
+                long_or_short: Short,
+                on_fill: Vec::new(),
+            }))
+            .unwrap();
+
+        let result = matcher.update(time, Quote::new(1.1, 1.2));
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            SimpleTransaction::OpenOrderFill(t) => assert_eq!(t.ticket.price, 1.2),
+            _ => panic!("unexpected transaction"),
+        }
+        assert_eq!(matcher.pending_orders().len(), 2);
+
+        // ask が指値以下、bid が逆指値以下になったので両方約定する
+        let result = matcher.update(time, Quote::new(0.85, 0.95));
+        assert_eq!(result.len(), 2);
+        assert_eq!(matcher.tickets().tickets().len(), 3);
+        assert_eq!(matcher.cancel(limit), Err(Error::InvalidOrderId(limit)));
+    }
+
+    #[test]
+    fn test_on_fill() {
+        let time = Time::<S5>::new(0).into();
+        let mut matcher = OrderMatcher::new(TransactionId(0), TicketId(0));
+        matcher
+            .submit(Order::Open(OpenOrder {
+                order_type: OrderType::Market,
+                unit: 100,
+                long_or_short: Long,
+                on_fill: vec![
+                    SimpleOrder::TakeProfit(SimpleTakeProfitOrder { price: 1.5 }),
+                    SimpleOrder::TrailingStopLoss(SimpleTrailingStopLossOrder { distance: 0.2 }),
+                ],
+            }))
+            .unwrap();
+        matcher.update(time, Quote::new(1.0, 1.0));
+        assert_eq!(matcher.pending_orders().len(), 2);
+
+        // 逆指値は 1.2 - 0.2 = 1.0 まで上がる
+        assert!(matcher.update(time, Quote::new(1.2, 1.2)).is_empty());
+        let result = matcher.update(time, Quote::new(1.0, 1.0));
+        assert_eq!(
+            result,
+            vec![close(
+                TransactionId(1),
+                TransactionId(0),
+                time,
+                TicketId(0),
+                100,
+                1.0
+            )]
+        );
+        // 決済済みの ticket に対する TP は取り消される
+        assert!(matcher.update(time, Quote::new(1.6, 1.6)).is_empty());
+        assert!(matcher.pending_orders().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_order() {
+        let time = Time::<S5>::new(0).into();
+        let mut matcher = OrderMatcher::new(TransactionId(0), TicketId(0));
+        assert_eq!(
+            matcher.submit(Order::market(0, Long)),
+            Err(Error::InvalidUnit(0))
+        );
+        assert_eq!(
+            matcher.submit(Order::close(TicketId(0), 0)),
+            Err(Error::InvalidUnit(0))
+        );
+        assert!(matcher.pending_orders().is_empty());
+
+        // 同じ ticket を二重に決済する注文は後の方が捨てられる
+        matcher.submit(Order::market(100, Long)).unwrap();
+        matcher.update(time, Quote::new(1.0, 1.0));
+        matcher.submit(Order::close(TicketId(0), 100)).unwrap();
+        matcher.submit(Order::close(TicketId(0), 100)).unwrap();
+        let result = matcher.update(time, Quote::new(1.0, 1.0));
+        assert_eq!(result.len(), 1);
+        assert!(matcher.tickets().is_empty());
+        assert!(matcher.pending_orders().is_empty());
+    }
+
+    #[test]
+    fn test_profit_loss() {
+        let time = Time::<S5>::new(0).into();
+        let offset = TransactionId(10);
+        let mut matcher = OrderMatcher::new(offset, TicketId(0));
+        let mut transactions = Vec::new();
+
+        matcher.submit(Order::market(100, Short)).unwrap();
+        transactions.extend(matcher.update(time, Quote::new(1.4, 1.5)));
+        matcher.submit(Order::close(TicketId(0), 40)).unwrap();
+        transactions.extend(matcher.update(time, Quote::new(1.1, 1.2)));
+        matcher.submit(Order::close(TicketId(0), 100)).unwrap();
+        transactions.extend(matcher.update(time, Quote::new(1.5, 1.6)));
+        assert!(matcher.tickets().is_empty());
+
+        let pl = ProfitLoss::new(TradeHistories::new(VecIndicator::new(offset, transactions)));
+        let result = (0..3).map(|i| pl.value(offset + i)).collect::<Vec<_>>();
+        let expect = vec![
+            MaybeFixed::Fixed(MaybeInRange::InRange(0.0)),
+            MaybeFixed::Fixed(MaybeInRange::InRange(8.0)),
+            MaybeFixed::Fixed(MaybeInRange::InRange(-12.0)),
+        ];
+        assert_relative_eq!(
+            result.as_slice(),
+            expect.as_slice(),
+            max_relative = 0.0000001
+        );
+    }
+}
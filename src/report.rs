@@ -0,0 +1,311 @@
+use crate::granularity::*;
+use crate::indicator::trade::*;
+use crate::indicator::*;
+use crate::seq::*;
+use crate::time::*;
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use MaybeFixed::*;
+use MaybeInRange::*;
+
+// 比率は小数 (0.1 = 10%)、期間は秒で持つ
+// 分母が 0 になる指標は None
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BacktestReport {
+    pub initial_equity: f64,
+    pub final_equity: f64,
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_duration: i64,
+    // 足ごとのリターンから求める (年率換算はしない)
+    pub sharpe_ratio: Option<f64>,
+    pub sortino_ratio: Option<f64>,
+    pub profit_factor: Option<f64>,
+    pub win_rate: f64,
+    pub average_win: f64,
+    pub average_loss: f64,
+    pub expectancy: f64,
+    // position を持っていた時間の割合
+    // 決済した trade だけから求めるので、end の時点でまだ持っている position は含まない
+    pub exposure_time: f64,
+    pub trade_count: usize,
+}
+
+impl BacktestReport {
+    // trades と pl は tid_offset から、equity は start から end の手前までを読む
+    pub fn new<G, IT, IP, IE>(
+        trades: &IT,
+        pl: &IP,
+        tid_offset: TransactionId,
+        equity: &IE,
+        start: Time<G>,
+        end: Time<G>,
+    ) -> Self
+    where
+        G: StaticGranularity,
+        IT: FuncIndicator<Seq = TransactionId, Val = Option<Trade>>,
+        IP: FuncIndicator<Seq = TransactionId, Val = f64>,
+        IE: FuncIndicator<Seq = Time<G>, Val = f64>,
+    {
+        let mut closed = Vec::new();
+        let mut seq = tid_offset;
+        loop {
+            match (trades.value(seq), pl.value(seq)) {
+                (Fixed(InRange(Some(trade))), Fixed(InRange(pl))) => closed.push((trade, pl)),
+                (Fixed(InRange(None)), Fixed(InRange(_))) => (),
+                _ => break,
+            }
+            seq = seq + 1;
+        }
+
+        let mut curve = Vec::new();
+        let mut seq = start;
+        while seq < end {
+            match equity.value(seq) {
+                Fixed(InRange(v)) => curve.push((seq.into(), v)),
+                // 市場が閉じている足は飛ばす
+                Fixed(OutOfRange) => (),
+                NotFixed => break,
+            }
+            seq = seq + 1;
+        }
+
+        Self::from_values(&closed, &curve, start.into(), end.into())
+    }
+
+    pub fn from_values(
+        trades: &[(Trade, f64)],
+        equity: &[(DateTime<Utc>, f64)],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Self {
+        let initial_equity = equity.first().map_or(0.0, |e| e.1);
+        let final_equity = equity.last().map_or(0.0, |e| e.1);
+        let total_return = if initial_equity == 0.0 {
+            0.0
+        } else {
+            (final_equity - initial_equity) / initial_equity
+        };
+        let (max_drawdown, max_drawdown_duration) = drawdown(equity, end);
+
+        let returns = equity
+            .windows(2)
+            .filter(|w| w[0].1 != 0.0)
+            .map(|w| w[1].1 / w[0].1 - 1.0)
+            .collect::<Vec<_>>();
+        let mean_return = mean(&returns);
+        let deviation = (returns
+            .iter()
+            .map(|r| (r - mean_return).powi(2))
+            .sum::<f64>()
+            / returns.len() as f64)
+            .sqrt();
+        let downside =
+            (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / returns.len() as f64).sqrt();
+
+        let wins = trades
+            .iter()
+            .map(|t| t.1)
+            .filter(|pl| *pl > 0.0)
+            .collect::<Vec<_>>();
+        // 損益が 0 の trade は勝ちにも負けにも数えない
+        let losses = trades
+            .iter()
+            .map(|t| t.1)
+            .filter(|pl| *pl < 0.0)
+            .collect::<Vec<_>>();
+        let gross_profit = wins.iter().sum::<f64>();
+        let gross_loss = -losses.iter().sum::<f64>();
+        let trade_count = trades.len();
+
+        Self {
+            initial_equity: initial_equity,
+            final_equity: final_equity,
+            total_return: total_return,
+            max_drawdown: max_drawdown,
+            max_drawdown_duration: max_drawdown_duration,
+            sharpe_ratio: ratio(mean_return, deviation),
+            sortino_ratio: ratio(mean_return, downside),
+            profit_factor: ratio(gross_profit, gross_loss),
+            win_rate: ratio(wins.len() as f64, trade_count as f64).unwrap_or(0.0),
+            average_win: mean(&wins),
+            average_loss: mean(&losses),
+            expectancy: ratio(gross_profit - gross_loss, trade_count as f64).unwrap_or(0.0),
+            exposure_time: exposure(trades, start, end),
+            trade_count: trade_count,
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    ratio(values.iter().sum(), values.len() as f64).unwrap_or(0.0)
+}
+
+fn ratio(a: f64, b: f64) -> Option<f64> {
+    if b == 0.0 || !b.is_finite() {
+        None
+    } else {
+        Some(a / b)
+    }
+}
+
+// 高値更新から次に高値を回復するまで (回復しなければ end まで) を drawdown 期間とする
+fn drawdown(equity: &[(DateTime<Utc>, f64)], end: DateTime<Utc>) -> (f64, i64) {
+    let mut max_drawdown = 0.0;
+    let mut max_duration = 0;
+    let mut peak = match equity.first() {
+        Some(e) => *e,
+        None => return (0.0, 0),
+    };
+    // 前の高値から一度でも下回っていれば drawdown 期間として数える
+    let mut is_below = false;
+    for (time, value) in equity.iter() {
+        if peak.1 <= *value {
+            if is_below {
+                max_duration = max_duration.max((*time - peak.0).num_seconds());
+            }
+            peak = (*time, *value);
+            is_below = false;
+        } else {
+            is_below = true;
+            if peak.1 > 0.0 {
+                max_drawdown = f64::max(max_drawdown, (peak.1 - value) / peak.1);
+            }
+        }
+    }
+    if is_below {
+        max_duration = max_duration.max((end - peak.0).num_seconds());
+    }
+    (max_drawdown, max_duration)
+}
+
+// 同時に持っている trade の期間は重複して数えない
+// 未決済の position は trade になっていないので数えない
+fn exposure(trades: &[(Trade, f64)], start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    let mut intervals = trades
+        .iter()
+        .map(|t| (t.0.open_time.max(start), t.0.close_time.min(end)))
+        .filter(|(open, close)| open < close)
+        .collect::<Vec<_>>();
+    intervals.sort();
+    let mut total = 0;
+    let mut current: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    for (open, close) in intervals {
+        current = match current {
+            Some((s, e)) if open <= e => Some((s, e.max(close))),
+            Some((s, e)) => {
+                total += (e - s).num_seconds();
+                Some((open, close))
+            }
+            None => Some((open, close)),
+        };
+    }
+    if let Some((s, e)) = current {
+        total += (e - s).num_seconds();
+    }
+    ratio(total as f64, (end - start).num_seconds() as f64).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicator::balance::*;
+    use crate::transaction::*;
+    use crate::vec::*;
+    use approx::assert_relative_eq;
+    use LongOrShort::*;
+
+    #[test]
+    fn test_report() {
+        let time = Time::<S5>::new(0);
+        let trade = |open: i64, close: i64, long_or_short, close_price| Trade {
+            unit: 100,
+            long_or_short: long_or_short,
+            open_time: (time + open).into(),
+            close_time: (time + close).into(),
+            open_price: 1.0,
+            close_price: close_price,
+            commission: 0.0,
+        };
+        let offset = TransactionId(0);
+        let trades = VecIndicator::new(
+            offset,
+            vec![
+                None,
+                Some(trade(0, 2, Long, 1.2)),
+                Some(trade(1, 3, Long, 0.9)),
+                None,
+                Some(trade(5, 6, Short, 0.7)),
+            ],
+        )
+        .into_sync_ptr();
+        let pl = ProfitLoss::new(trades.clone());
+        let equity = VecIndicator::new(
+            time,
+            vec![100.0, 110.0, 120.0, 110.0, 105.0, 115.0, 140.0, 130.0],
+        );
+        let report = BacktestReport::new(&trades, &pl, offset, &equity, time, time + 8);
+
+        assert_relative_eq!(report.total_return, 0.3);
+        assert_relative_eq!(report.max_drawdown, 15.0 / 120.0);
+        // 120 になった足から 140 になる足まで
+        assert_eq!(report.max_drawdown_duration, 20);
+        assert_eq!(report.trade_count, 3);
+        assert_relative_eq!(report.win_rate, 2.0 / 3.0);
+        assert_relative_eq!(report.average_win, 25.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.average_loss, -10.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.expectancy, 40.0 / 3.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.profit_factor.unwrap(), 5.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.exposure_time, 4.0 / 8.0);
+        assert!(report.sharpe_ratio.unwrap() > 0.0);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"max_drawdown_duration\":20"));
+        let decoded: BacktestReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.trade_count, report.trade_count);
+        assert_relative_eq!(decoded.total_return, report.total_return);
+
+        // 高値を更新し続けるだけなら drawdown 期間は無い
+        let rising = VecIndicator::new(time, vec![100.0, 110.0, 120.0]);
+        let report = BacktestReport::new(&trades, &pl, offset, &rising, time, time + 3);
+        assert_eq!(report.max_drawdown, 0.0);
+        assert_eq!(report.max_drawdown_duration, 0);
+    }
+
+    #[test]
+    fn test_report_even_trade() {
+        let time = Time::<S5>::new(0);
+        let trade = |open: i64, close: i64, close_price| Trade {
+            unit: 100,
+            long_or_short: Long,
+            open_time: (time + open).into(),
+            close_time: (time + close).into(),
+            open_price: 1.0,
+            close_price: close_price,
+            commission: 0.0,
+        };
+        let offset = TransactionId(0);
+        let trades = VecIndicator::new(
+            offset,
+            vec![
+                Some(trade(0, 1, 1.2)),
+                Some(trade(1, 2, 1.0)),
+                Some(trade(2, 3, 0.9)),
+                // end の時点で未決済の position は trade に現れない
+                None,
+            ],
+        )
+        .into_sync_ptr();
+        let pl = ProfitLoss::new(trades.clone());
+        let equity = VecIndicator::new(time, vec![100.0, 120.0, 120.0, 110.0, 115.0, 120.0]);
+        let report = BacktestReport::new(&trades, &pl, offset, &equity, time, time + 6);
+
+        // 損益 0 の trade は平均損失に含めない
+        assert_eq!(report.trade_count, 3);
+        assert_relative_eq!(report.win_rate, 1.0 / 3.0);
+        assert_relative_eq!(report.average_win, 20.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.average_loss, -10.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.expectancy, 10.0 / 3.0, max_relative = 0.0000001);
+        assert_relative_eq!(report.exposure_time, 3.0 / 6.0);
+    }
+}
@@ -1,4 +1,30 @@
-pub trait Strategy {}
+use crate::candle::BidAskCandle;
+use crate::granularity::StaticGranularity;
+use crate::order::{Order, Quote};
+use crate::seq::TickId;
+use crate::ticket::TicketBook;
+use crate::time::Time;
+use crate::transaction::SimpleTransaction;
+
+pub trait Strategy {
+    type Granularity: StaticGranularity;
+
+    // 足が確定したときにデータを取り込む
+    fn on_bar(&mut self, time: Time<Self::Granularity>, candle: BidAskCandle);
+
+    // 価格が更新されたときに発注する注文を返す
+    // tick_id は通し番号で、その tick の時点で確定している最新の足 (直前に on_bar に渡した足) に対応する
+    fn on_tick(
+        &mut self,
+        tick_id: TickId,
+        time: chrono::DateTime<chrono::Utc>,
+        quote: Quote,
+        tickets: &TicketBook,
+    ) -> Vec<Order>;
+
+    // 約定した transaction を受け取る
+    fn on_transaction(&mut self, _transaction: &SimpleTransaction) {}
+}
 
 pub mod simple;
 pub mod busena;
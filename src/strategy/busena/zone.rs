@@ -40,6 +40,21 @@ impl ZoneId {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ZoneError {
+    Overlapped(ZoneId, ZoneId),
+}
+
+impl std::fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ZoneError::Overlapped(p, n) => write!(f, "overlapped zone: {:?}, {:?}", p, n),
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
 pub struct Zone<I1, I2> {
     price: I1,
     positive_lines: Vec<I2>,
@@ -89,6 +104,19 @@ where
         }
         Fixed(InRange(Some(ZoneId(-1 * self.negative_lines.len() as i32))))
     }
+
+    // positive_lines と negative_lines が交差している場合は Err
+    pub fn try_value(&self, seq: S) -> MaybeValue<Result<ZoneId, ZoneError>> {
+        let p_zone = try_value!(self.check_positive(seq));
+        let n_zone = try_value!(self.check_negative(seq));
+        let v = match (p_zone, n_zone) {
+            (Some(p), Some(n)) => Err(ZoneError::Overlapped(p, n)),
+            (Some(z), None) => Ok(z),
+            (None, Some(z)) => Ok(z),
+            (None, None) => Ok(ZoneId(0)),
+        };
+        Fixed(InRange(v))
+    }
 }
 
 impl<S, I1, I2> Indicator for Zone<I1, I2>
@@ -108,13 +136,9 @@ where
     I2: FuncIndicator<Seq = S, Val = f64>,
 {
     fn value(&self, seq: Self::Seq) -> MaybeValue<Self::Val> {
-        let p_zone = try_value!(self.check_positive(seq));
-        let n_zone = try_value!(self.check_negative(seq));
-        match (p_zone, n_zone) {
-            (Some(_), Some(_)) => panic!(""),
-            (Some(z), None) => Fixed(InRange(z)),
-            (None, Some(z)) => Fixed(InRange(z)),
-            (None, None) => Fixed(InRange(ZoneId(0))),
+        match try_value!(self.try_value(seq)) {
+            Ok(z) => Fixed(InRange(z)),
+            Err(e) => panic!("{}", e),
         }
     }
 }
@@ -209,7 +233,9 @@ where
     I: FuncIndicator<Seq = S, Val = f64>,
 {
     source.rolling(2, |w| {
-        let diff = try_value!(w.rfold(0.0, |x, acc| x - acc));
+        let prev = try_value!(w.value(0));
+        let curr = try_value!(w.value(1));
+        let diff = curr - prev;
         let ud = if diff > 0.0 {
             UpDown::Up
         } else if diff < 0.0 {
@@ -370,6 +396,26 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn test_zone_overlapped() {
+        let offset = TickId(0);
+        let expect = vec![
+            Fixed(InRange(Ok(ZoneId(0)))),
+            Fixed(InRange(Err(ZoneError::Overlapped(ZoneId(1), ZoneId(-1))))),
+        ];
+
+        let price = VecIndicator::new(offset, vec![1.0, 2.0]);
+        let env_p1 = VecIndicator::new(offset, vec![1.1, 1.9]);
+        let env_m1 = VecIndicator::new(offset, vec![0.9, 2.1]);
+
+        let zone = Zone::new(price, vec![env_p1], vec![env_m1]);
+
+        let result = (0..2)
+            .map(|i| zone.try_value(offset + i))
+            .collect::<Vec<_>>();
+        assert_eq!(result, expect);
+    }
+
     #[test]
     fn test_up_down() {
         use UpDown::*;
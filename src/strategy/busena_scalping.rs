@@ -2,6 +2,7 @@ use super::busena::signal::*;
 use super::busena::status::*;
 use super::busena::zone::*;
 use super::*;
+use crate::candle::*;
 use crate::granularity::*;
 use crate::indicator::balance::*;
 use crate::indicator::cached::*;
@@ -19,6 +20,7 @@ use crate::indicator::trade::*;
 use crate::indicator::vec::*;
 use crate::indicator::FuncIndicator;
 use crate::indicator::*;
+use crate::order::*;
 use crate::position::*;
 use crate::seq::*;
 use crate::signal::*;
@@ -38,6 +40,7 @@ pub struct BusenaScalpingStrategy {
     // time
     time_tick: Rc<RefCell<VecIndicator<TickId, Time<M1>>>>,
     latest_time: Option<Time<M1>>,
+    next_tick_id: TickId,
 
     // price
     mid_close_m1: Rc<RefCell<Storage<Time<M1>, f64>>>,
@@ -151,6 +154,7 @@ impl BusenaScalpingStrategy {
 
         Self {
             latest_time: None,
+            next_tick_id: offset_tick,
             time_tick: time_tick,
             mid_tick: mid_tick,
             mid_close_m1: mid_close_m1,
@@ -201,20 +205,53 @@ impl BusenaScalpingStrategy {
 
         let env_p1 = try_value!(self.envelopes_tick_p[0].borrow().value(tick_id));
         let env_m1 = try_value!(self.envelopes_tick_m[0].borrow().value(tick_id));
-        println!("env_p1: {}, env_m1: {}", env_p1, env_m1);
-        println!("zone: {:?}, {:?}", outermost_zone, zone);
-        println!("up_down: {:?}, {:?}", up_down, up_down_count);
-        println!("signal: {:?}", signal);
+        debug!("env_p1: {}, env_m1: {}", env_p1, env_m1);
+        debug!("zone: {:?}, {:?}", outermost_zone, zone);
+        debug!("up_down: {:?}, {:?}", up_down, up_down_count);
+        debug!("signal: {:?}", signal);
         // println!("{:?}", self.status);
 
         Fixed(InRange(()))
     }
 
-    pub fn on_tick(&mut self, tick_id: TickId, mid: f64, bid: f64, ask: f64) {
+}
+
+impl Strategy for BusenaScalpingStrategy {
+    type Granularity = M1;
+
+    fn on_bar(&mut self, time: Time<M1>, candle: BidAskCandle) {
+        self.add_price_m1(time, candle.mid.close);
+    }
+
+    fn on_tick(
+        &mut self,
+        tick_id: TickId,
+        _time: DateTime<Utc>,
+        quote: Quote,
+        _tickets: &TicketBook,
+    ) -> Vec<Order> {
+        // on_bar より前に tick は来ない
+        let latest_time = match self.latest_time {
+            Some(t) => t,
+            None => return Vec::new(),
+        };
+        // time_tick と mid_tick は tick_id の順に詰めて追加するので番号が飛んだ tick は使えない
+        if tick_id != self.next_tick_id {
+            error!("unexpected tick_id: {:?}, {:?}", tick_id, self.next_tick_id);
+            return Vec::new();
+        }
+        self.next_tick_id = tick_id + 1;
+
         // add time, prices
-        self.time_tick.borrow_mut().add(self.latest_time.unwrap());
+        let mid = quote.mid();
+        self.time_tick.borrow_mut().add(latest_time);
         self.mid_tick.borrow_mut().add(mid);
 
-        self.on_tick_inner(tick_id, mid, bid, ask);
+        self.on_tick_inner(tick_id, mid, quote.bid, quote.ask);
+        match self.get_signal(tick_id) {
+            Fixed(InRange(LotSignal::Buy(lot))) => vec![Order::market(lot, LongOrShort::Long)],
+            Fixed(InRange(LotSignal::Sell(lot))) => vec![Order::market(lot, LongOrShort::Short)],
+            _ => Vec::new(),
+        }
     }
 }
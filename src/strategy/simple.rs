@@ -1,15 +1,17 @@
 use super::*;
+use crate::candle::*;
 use crate::granularity::*;
-use crate::indicator::balance::*;
+use crate::indicator::cached::*;
 use crate::indicator::complement::*;
 use crate::indicator::convert_seq::*;
 use crate::indicator::convert_seq::*;
 use crate::indicator::cross::*;
+use crate::indicator::equity::*;
 use crate::indicator::sma::*;
 use crate::indicator::storage::*;
-use crate::indicator::trade::*;
 use crate::indicator::vec::*;
 use crate::indicator::*;
+use crate::order::*;
 use crate::position::*;
 use crate::seq::*;
 use crate::signal::*;
@@ -20,7 +22,6 @@ use crate::transaction::*;
 use chrono::prelude::*;
 use log::*;
 use std::cell::RefCell;
-use std::convert::TryFrom;
 use std::rc::Rc;
 use MaybeFixed::*;
 use MaybeInRange::*;
@@ -30,51 +31,43 @@ pub struct SimpleStrategyBase {}
 type Base = SimpleStrategyBase;
 pub struct SimpleSmaCrossStrategy {
     base: Base,
-    mid_close: Rc<RefCell<Storage<Time<S5>, f64>>>,
-    bid_close: Rc<RefCell<Storage<Time<S5>, f64>>>,
-    ask_close: Rc<RefCell<Storage<Time<S5>, f64>>>,
+    candle: Rc<RefCell<Storage<Time<S5>, BidAskCandle>>>,
     // mid_close: Rc<RefCell<VecIndicator<Time<S5>, Option<f64>>>>,
     // bid_close: Rc<RefCell<VecIndicator<Time<S5>, Option<f64>>>>,
     // ask_close: Rc<RefCell<VecIndicator<Time<S5>, Option<f64>>>>,
-    bid_close_cmpl: Rc<RefCell<dyn FuncIndicator<Seq = Time<S5>, Val = f64>>>,
-    ask_close_cmpl: Rc<RefCell<dyn FuncIndicator<Seq = Time<S5>, Val = f64>>>,
     transaction: Rc<RefCell<VecIndicator<TransactionId, SimpleTransaction>>>,
-    trade: Rc<RefCell<dyn FuncIndicator<Seq = TransactionId, Val = Option<Trade>>>>,
     signal: Box<dyn IterIndicator<Seq = Time<S5>, Val = SimpleSignal>>,
-    tid_offset: TransactionId,
-    ticket_id_offset: TicketId,
-    single_ticket: Rc<RefCell<SingleSimpleTicket>>,
-    balance: Box<dyn IterIndicator<Seq = Time<S5>, Val = f64>>,
+    balance: Rc<RefCell<dyn FuncIndicator<Seq = Time<S5>, Val = f64>>>,
+    latest_time: Option<Time<S5>>,
 }
 
 impl SimpleSmaCrossStrategy {
     pub fn new(base: Base, time_offset: Time<S5>, tid_offset: TransactionId) -> Self {
-        let mid_close = Storage::new(time_offset).into_sync_ptr();
-        let bid_close = Storage::new(time_offset).into_sync_ptr();
-        let ask_close = Storage::new(time_offset).into_sync_ptr();
+        let candle = Storage::new(time_offset).into_sync_ptr();
         // let mid_close = VecIndicator::new(time_offset, Vec::with_capacity(1000)).into_sync_ptr();
         // let bid_close = VecIndicator::new(time_offset, Vec::with_capacity(1000)).into_sync_ptr();
         // let ask_close = VecIndicator::new(time_offset, Vec::with_capacity(1000)).into_sync_ptr();
 
-        let mid_close_cmpl = ComplementWithLastValue::new(mid_close.clone(), 100).into_sync_ptr();
-        let bid_close_cmpl = ComplementWithLastValue::new(bid_close.clone(), 100).into_sync_ptr();
-        let ask_close_cmpl = ComplementWithLastValue::new(ask_close.clone(), 100).into_sync_ptr();
+        let candle_cmpl = ComplementWithLastValue::new(candle.clone(), 100).into_sync_ptr();
+        let mid_close_cmpl = candle_cmpl.clone().mid().close().into_sync_ptr();
         // let mid_close_cmpl_2 = mid_close
         //     .clone()
         //     .map(|v| v.or(Some(0.0)).unwrap())
         //     .when_not_fixed(|| Fixed(InRange(0.0)))
         //     .into_sync_ptr();
 
-        let sma_short = sma(mid_close_cmpl.clone(), 25);
-        let sma_long = sma(mid_close_cmpl.clone(), 75);
+        let sma_short = LRUCache::new(10, sma(mid_close_cmpl.clone(), 25));
+        let sma_long = LRUCache::new(10, sma(mid_close_cmpl.clone(), 75));
         let sma_cross = Cross::new(sma_short, sma_long).into_sync_ptr();
 
-        let transaction = VecIndicator::new(tid_offset, vec![]).into_sync_ptr();
+        let transaction = VecIndicator::new(tid_offset, Vec::new()).into_sync_ptr();
 
-        let single_ticket = Rc::new(RefCell::new(SingleSimpleTicket::new()));
-        let st = single_ticket.clone();
+        let ticket_book = Rc::new(RefCell::new(TicketBook::new(NettingMode::Hedging)));
+        let st = ticket_book.clone();
         let position_tid = transaction.clone().into_iter(tid_offset).map(move |t| {
-            st.borrow_mut().apply_transaction(t);
+            if let Err(e) = st.borrow_mut().try_apply_transaction(t) {
+                error!("{}", e);
+            }
             st.borrow().as_position()
         });
         let position_time = {
@@ -89,22 +82,12 @@ impl SimpleSmaCrossStrategy {
             })
         };
 
-        let trade = TradeHistories::new(transaction.clone()).into_sync_ptr();
-        let pl = ProfitLoss::new(trade.clone()).into_sync_ptr();
-        let mut sum = 0.0;
-        let balance = Consume::new(time_offset, pl.clone().into_iter(tid_offset), |i| {
-            let v = i.into_std().fold(0.0, |acc, v| acc + v);
-            Fixed(InRange(v))
-        })
-        .map(move |v| {
-            sum += v;
-            // debug!("balance: {:?}", sum);
-            if v != 0.0 {
-                info!("balance: {:?}", sum);
-            }
-            sum
-        })
-        .into_sync_ptr();
+        // 初期残高によらない quote 通貨建ての累積の実現損益
+        let rate = ClosureIndicator::new(|_| Fixed(InRange(1.0)));
+        // on_bar で足を受け取った時点でその足までの約定は届いている
+        let balance =
+            RealizedBalance::new(transaction.clone(), candle.clone(), rate, 0.0, tid_offset)
+                .into_sync_ptr();
 
         let signal = sma_cross
             .clone()
@@ -125,36 +108,11 @@ impl SimpleSmaCrossStrategy {
 
         Self {
             base: base,
-            mid_close: mid_close,
-            bid_close: bid_close,
-            ask_close: ask_close,
-            bid_close_cmpl: bid_close_cmpl,
-            ask_close_cmpl: ask_close_cmpl,
+            candle: candle,
             transaction: transaction,
-            trade: trade,
             signal: signal,
-            tid_offset: tid_offset,
-            ticket_id_offset: TicketId(0),
-            single_ticket: single_ticket,
             balance: balance,
-        }
-    }
-
-    pub fn update_source(
-        &mut self,
-        time: DateTime<Utc>,
-        mid_close: f64,
-        bid_close: f64,
-        ask_close: f64,
-    ) {
-        match <Time<S5>>::try_from(time) {
-            Ok(t) => {
-                // println!("{:?},{:?},{:?}", mid_close, bid_close, ask_close);
-                self.mid_close.borrow_mut().add(t, mid_close);
-                self.bid_close.borrow_mut().add(t, bid_close);
-                self.ask_close.borrow_mut().add(t, ask_close);
-            }
-            Err(_) => panic!("invalid time"),
+            latest_time: None,
         }
     }
 
@@ -175,29 +133,37 @@ impl SimpleSmaCrossStrategy {
     //         Err(_) => panic!("invalid time"),
     //     }
     // }
+}
 
-    fn next_tid(&mut self) -> TransactionId {
-        let tid = self.tid_offset;
-        self.tid_offset = tid + 1;
-        tid
-    }
+impl Strategy for SimpleSmaCrossStrategy {
+    type Granularity = S5;
 
-    fn next_ticket_id(&mut self) -> TicketId {
-        let ticket_id = self.ticket_id_offset;
-        self.ticket_id_offset = ticket_id + 1;
-        ticket_id
+    fn on_bar(&mut self, time: Time<S5>, candle: BidAskCandle) {
+        self.latest_time = Some(time);
+        self.candle.borrow_mut().add(time, candle);
     }
 
-    pub fn on_tick(&mut self, time: DateTime<Utc>) {
-        let time_s5 = match <Time<S5>>::try_from(time) {
-            Ok(t) => t,
-            Err(_) => return,
+    fn on_tick(
+        &mut self,
+        _tick_id: TickId,
+        _time: DateTime<Utc>,
+        _quote: Quote,
+        tickets: &TicketBook,
+    ) -> Vec<Order> {
+        // 確定した最新の足の signal を使う
+        let time_s5 = match self.latest_time {
+            Some(t) => t,
+            None => return Vec::new(),
         };
 
-        self.balance.next();
-        let dt: DateTime<Utc> = self.signal.offset().into();
-        // println!("signal offset: {:?}", dt);
-        let signal = self.signal.next();
+        // データの無い足の分も進めて time_s5 の signal を取り出す
+        let mut signal = NotFixed;
+        while self.signal.offset() <= time_s5 {
+            signal = self.signal.next();
+            if signal.is_not_fixed() {
+                break;
+            }
+        }
         match signal {
             Fixed(InRange(s)) => match s {
                 SimpleSignal::Nothing => (),
@@ -206,156 +172,47 @@ impl SimpleSmaCrossStrategy {
             _ => (),
         }
         use LongOrShort::*;
+        let close_all = || {
+            tickets
+                .tickets()
+                .iter()
+                .map(|t| Order::close(t.id, t.unit))
+                .collect::<Vec<_>>()
+        };
         match signal {
             Fixed(InRange(s)) => match s {
-                SimpleSignal::OpenLong => {
-                    let t = SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                        id: self.next_tid(),
-                        time: time,
-                        ticket: SimpleTicket {
-                            id: self.next_ticket_id(),
-                            open_time: time,
-                            unit: 100,
-                            price: self
-                                .ask_close_cmpl
-                                .borrow()
-                                .value(time_s5)
-                                .unwrap()
-                                .unwrap(),
-                            long_or_short: Long,
-                        },
-                    });
-                    self.transaction.borrow_mut().add(t);
-                }
-                SimpleSignal::OpenShort => {
-                    let t = SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                        id: self.next_tid(),
-                        time: time,
-                        ticket: SimpleTicket {
-                            id: self.next_ticket_id(),
-                            open_time: time,
-                            unit: 100,
-                            price: self
-                                .bid_close_cmpl
-                                .borrow()
-                                .value(time_s5)
-                                .unwrap()
-                                .unwrap(),
-                            long_or_short: Short,
-                        },
-                    });
-                    self.transaction.borrow_mut().add(t);
-                }
-                SimpleSignal::CloseLong => {
-                    let tid = self.next_tid();
-                    let t = SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                        id: tid,
-                        open_id: tid - 1,
-                        time: time,
-                        ticket_id: self.single_ticket.borrow().ticket().unwrap().id,
-                        unit: 100,
-                        price: self
-                            .bid_close_cmpl
-                            .borrow()
-                            .value(time_s5)
-                            .unwrap()
-                            .unwrap(),
-                    });
-                    self.transaction.borrow_mut().add(t);
-                }
-                SimpleSignal::CloseShort => {
-                    let tid = self.next_tid();
-                    let t = SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                        id: tid,
-                        open_id: tid - 1,
-                        time: time,
-                        ticket_id: self.single_ticket.borrow().ticket().unwrap().id,
-                        unit: 100,
-                        price: self
-                            .ask_close_cmpl
-                            .borrow()
-                            .value(time_s5)
-                            .unwrap()
-                            .unwrap(),
-                    });
-                    self.transaction.borrow_mut().add(t);
-                }
+                SimpleSignal::OpenLong => vec![Order::market(100, Long)],
+                SimpleSignal::OpenShort => vec![Order::market(100, Short)],
+                SimpleSignal::CloseLong | SimpleSignal::CloseShort => close_all(),
                 SimpleSignal::CloseLongAndOpenShort => {
-                    let tid = self.next_tid();
-                    let t = SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                        id: tid,
-                        open_id: tid - 1,
-                        time: time,
-                        ticket_id: self.single_ticket.borrow().ticket().unwrap().id,
-                        unit: 100,
-                        price: self
-                            .bid_close_cmpl
-                            .borrow()
-                            .value(time_s5)
-                            .unwrap()
-                            .unwrap(),
-                    });
-                    self.transaction.borrow_mut().add(t);
-                    let t = SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                        id: self.next_tid(),
-                        time: time,
-                        ticket: SimpleTicket {
-                            id: self.next_ticket_id(),
-                            open_time: time,
-                            unit: 100,
-                            price: self
-                                .bid_close_cmpl
-                                .borrow()
-                                .value(time_s5)
-                                .unwrap()
-                                .unwrap(),
-                            long_or_short: Short,
-                        },
-                    });
-                    self.transaction.borrow_mut().add(t);
+                    let mut orders = close_all();
+                    orders.push(Order::market(100, Short));
+                    orders
                 }
                 SimpleSignal::CloseShortAndOpenLong => {
-                    let tid = self.next_tid();
-                    let t = SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                        id: tid,
-                        open_id: tid - 1,
-                        time: time,
-                        ticket_id: self.single_ticket.borrow().ticket().unwrap().id,
-                        unit: 100,
-                        price: self
-                            .ask_close_cmpl
-                            .borrow()
-                            .value(time_s5)
-                            .unwrap()
-                            .unwrap(),
-                    });
-                    self.transaction.borrow_mut().add(t);
-                    let t = SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                        id: self.next_tid(),
-                        time: time,
-                        ticket: SimpleTicket {
-                            id: self.next_ticket_id(),
-                            open_time: time,
-                            unit: 100,
-                            price: self
-                                .ask_close_cmpl
-                                .borrow()
-                                .value(time_s5)
-                                .unwrap()
-                                .unwrap(),
-                            long_or_short: Long,
-                        },
-                    });
-                    self.transaction.borrow_mut().add(t);
+                    let mut orders = close_all();
+                    orders.push(Order::market(100, Long));
+                    orders
                 }
-                _ => (),
+                _ => Vec::new(),
             },
             NotFixed => {
                 debug!("signal is not fixed");
+                Vec::new()
             }
             Fixed(OutOfRange) => {
                 debug!("signal is out of range");
+                Vec::new()
             }
-        };
+        }
+    }
+
+    fn on_transaction(&mut self, transaction: &SimpleTransaction) {
+        self.transaction.borrow_mut().add(transaction.clone());
+        // 残高はこの transaction の足の手前までしか確定していない
+        if let SimpleTransaction::CloseOrderFill(t) = transaction {
+            let time = Time::<S5>::truncate(t.time.timestamp());
+            info!("balance before close: {:?}", self.balance.borrow().value(time - 1));
+        }
     }
 }
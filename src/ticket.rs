@@ -1,7 +1,9 @@
 use crate::position::*;
 use crate::seq::*;
 use crate::transaction::*;
+use crate::Error;
 use chrono::prelude::*;
+use std::collections::HashMap;
 use std::ops::Add;
 use std::ops::Sub;
 
@@ -58,24 +60,48 @@ impl SingleSimpleTicket {
         Self { ticket: None }
     }
 
-    // TODO: unuse panic
     pub fn apply_transaction(&mut self, transaction: SimpleTransaction) {
+        if let Err(e) = self.try_apply_transaction(transaction) {
+            panic!("{}", e);
+        }
+    }
+
+    // 不正な transaction の場合は状態を変更せずに Err を返す
+    pub fn try_apply_transaction(&mut self, transaction: SimpleTransaction) -> Result<(), Error> {
         match transaction {
-            SimpleTransaction::OpenOrderFill(t) => match self.ticket {
-                Some(_) => panic!("invalid transaction"),
-                None => self.ticket = Some(t.ticket),
+            SimpleTransaction::OpenOrderFill(t) => match &mut self.ticket {
+                // 同じ ticket への追加建て
+                Some(ticket)
+                    if ticket.id == t.ticket.id
+                        && ticket.long_or_short == t.ticket.long_or_short
+                        && t.ticket.unit > 0 =>
+                {
+                    ticket.scale_in(t.ticket.unit, t.ticket.price);
+                    Ok(())
+                }
+                Some(_) => Err(Error::InvalidTransaction(t.id)),
+                None => {
+                    self.ticket = Some(t.ticket);
+                    Ok(())
+                }
             },
             SimpleTransaction::CloseOrderFill(t) => match &mut self.ticket {
                 Some(ticket) => {
-                    if t.ticket_id == ticket.id {
-                        self.ticket = None;
+                    if t.ticket_id != ticket.id {
+                        Err(Error::InvalidTicketId(t.ticket_id))
+                    } else if t.unit == 0 || ticket.unit < t.unit {
+                        Err(Error::InvalidTransaction(t.id))
                     } else {
-                        panic!("invalid ticket_id: {:?}", t.ticket_id)
+                        ticket.unit -= t.unit;
+                        if ticket.unit == 0 {
+                            self.ticket = None;
+                        }
+                        Ok(())
                     }
                 }
-                None => panic!("invalid transaction"),
+                None => Err(Error::InvalidTransaction(t.id)),
             },
-            _ => (),
+            _ => Ok(()),
         }
     }
 
@@ -94,13 +120,190 @@ impl SingleSimpleTicket {
     }
 }
 
+impl SimpleTicket {
+    // 建値は unit で加重平均する
+    pub fn scale_in(&mut self, unit: usize, price: f64) {
+        let total = self.unit + unit;
+        self.price = (self.price * self.unit as f64 + price * unit as f64) / total as f64;
+        self.unit = total;
+    }
+
+    pub fn unrealized_pl(&self, price: f64) -> f64 {
+        let distance = match self.long_or_short {
+            LongOrShort::Long => price - self.price,
+            LongOrShort::Short => self.price - price,
+        };
+        distance * self.unit as f64
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NettingMode {
+    Hedging,
+    Fifo,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TicketBook {
+    mode: NettingMode,
+    tickets: Vec<SimpleTicket>,
+    open_ids: HashMap<TicketId, TransactionId>,
+}
+
+impl TicketBook {
+    pub fn new(mode: NettingMode) -> Self {
+        Self {
+            mode: mode,
+            tickets: Vec::new(),
+            open_ids: HashMap::new(),
+        }
+    }
+
+    pub fn mode(&self) -> NettingMode {
+        self.mode
+    }
+
+    pub fn apply_transaction(&mut self, transaction: SimpleTransaction) {
+        if let Err(e) = self.try_apply_transaction(transaction) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn try_apply_transaction(&mut self, transaction: SimpleTransaction) -> Result<(), Error> {
+        match transaction {
+            SimpleTransaction::OpenOrderFill(t) => self.open(t),
+            SimpleTransaction::CloseOrderFill(t) => self.close(t),
+            _ => Ok(()),
+        }
+    }
+
+    fn open(&mut self, t: OpenOrderFillTransaction) -> Result<(), Error> {
+        if t.ticket.unit == 0 {
+            return Err(Error::InvalidTransaction(t.id));
+        }
+        // 同じ ticket への追加建て
+        if let Some(ticket) = self.tickets.iter_mut().find(|x| x.id == t.ticket.id) {
+            if ticket.long_or_short != t.ticket.long_or_short {
+                return Err(Error::InvalidTransaction(t.id));
+            }
+            ticket.scale_in(t.ticket.unit, t.ticket.price);
+            return Ok(());
+        }
+        // FIFO では反対方向の ticket を先に決済しなければならない
+        // 相殺した分の損益が CloseOrderFill に残らなくなるので open では相殺しない
+        if self.mode == NettingMode::Fifo
+            && self
+                .tickets
+                .iter()
+                .any(|x| x.long_or_short != t.ticket.long_or_short)
+        {
+            return Err(Error::InvalidTransaction(t.id));
+        }
+        self.open_ids.insert(t.ticket.id, t.id);
+        self.tickets.push(t.ticket);
+        Ok(())
+    }
+
+    fn close(&mut self, t: CloseOrderFillTransaction) -> Result<(), Error> {
+        let i = match self.tickets.iter().position(|x| x.id == t.ticket_id) {
+            Some(i) => i,
+            None => return Err(Error::InvalidTicketId(t.ticket_id)),
+        };
+        // FIFO では一番古い ticket からしか決済できない
+        if self.mode == NettingMode::Fifo && i != 0 {
+            return Err(Error::InvalidTicketId(t.ticket_id));
+        }
+        let ticket = &mut self.tickets[i];
+        if t.unit == 0 || ticket.unit < t.unit {
+            return Err(Error::InvalidTransaction(t.id));
+        }
+        ticket.unit -= t.unit;
+        if ticket.unit == 0 {
+            self.tickets.remove(i);
+            self.open_ids.remove(&t.ticket_id);
+        }
+        Ok(())
+    }
+
+    pub fn tickets(&self) -> &[SimpleTicket] {
+        &self.tickets
+    }
+
+    pub fn ticket(&self, id: TicketId) -> Option<SimpleTicket> {
+        self.tickets.iter().find(|x| x.id == id).cloned()
+    }
+
+    // ticket を最初に建てた transaction
+    pub fn open_id(&self, id: TicketId) -> Option<TransactionId> {
+        self.open_ids.get(&id).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    // long を正、short を負とした合計 unit
+    pub fn net_unit(&self) -> i64 {
+        self.tickets
+            .iter()
+            .map(|x| match x.long_or_short {
+                LongOrShort::Long => x.unit as i64,
+                LongOrShort::Short => -(x.unit as i64),
+            })
+            .sum()
+    }
+
+    pub fn as_position(&self) -> SimplePosition {
+        let net = self.net_unit();
+        if net > 0 {
+            SimplePosition::Long
+        } else if net < 0 {
+            SimplePosition::Short
+        } else {
+            SimplePosition::Nothing
+        }
+    }
+
+    pub fn average_price(&self, long_or_short: LongOrShort) -> Option<f64> {
+        let (unit, amount) = self
+            .tickets
+            .iter()
+            .filter(|x| x.long_or_short == long_or_short)
+            .fold((0, 0.0), |(u, a), x| {
+                (u + x.unit, a + x.price * x.unit as f64)
+            });
+        if unit == 0 {
+            None
+        } else {
+            Some(amount / unit as f64)
+        }
+    }
+
+    pub fn unrealized_pls(&self, price: f64) -> Vec<(TicketId, f64)> {
+        self.tickets
+            .iter()
+            .map(|x| (x.id, x.unrealized_pl(price)))
+            .collect()
+    }
+
+    pub fn unrealized_pl(&self, price: f64) -> f64 {
+        self.tickets.iter().map(|x| x.unrealized_pl(price)).sum()
+    }
+}
+
+impl From<TicketBook> for SimplePosition {
+    fn from(book: TicketBook) -> Self {
+        book.as_position()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::granularity::*;
     use crate::indicator::*;
-    use crate::seq::*;
     use crate::time::*;
+    use crate::transaction::builder::*;
     use crate::vec::*;
     use LongOrShort::*;
 
@@ -111,44 +314,10 @@ mod tests {
         VecIndicator::new(
             offset,
             vec![
-                SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                    id: offset + 0,
-                    time: (time + 0).into(),
-                    ticket: SimpleTicket {
-                        id: TicketId(3),
-                        open_time: (time + 0).into(),
-                        unit: 100,
-                        price: 1.234,
-                        long_or_short: Long,
-                    },
-                }),
-                SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                    id: offset + 1,
-                    open_id: offset + 0,
-                    time: (time + 5).into(),
-                    ticket_id: TicketId(3),
-                    unit: 100,
-                    price: 1.5,
-                }),
-                SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
-                    id: offset + 2,
-                    time: (time + 7).into(),
-                    ticket: SimpleTicket {
-                        id: TicketId(4),
-                        open_time: (time + 7).into(),
-                        unit: 100,
-                        price: 1.4,
-                        long_or_short: Short,
-                    },
-                }),
-                SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
-                    id: offset + 3,
-                    open_id: offset + 1,
-                    time: (time + 9).into(),
-                    ticket_id: TicketId(4),
-                    unit: 100,
-                    price: 1.1,
-                }),
+                open(offset + 0, time + 0, TicketId(3), 100, 1.234, Long),
+                close(offset + 1, offset + 0, time + 5, TicketId(3), 100, 1.5),
+                open(offset + 2, time + 7, TicketId(4), 100, 1.4, Short),
+                close(offset + 3, offset + 1, time + 9, TicketId(4), 100, 1.1),
             ],
         )
     }
@@ -212,4 +381,148 @@ mod tests {
         ];
         assert_eq!(positions, expect);
     }
+
+    #[test]
+    fn test_try_apply_transaction() {
+        let offset = TransactionId(10);
+        let time = Time::<S5>::new(0);
+
+        let source = get_source(offset, time);
+        let mut single_ticket = SingleSimpleTicket::new();
+
+        let close = source.value(offset + 1).unwrap().unwrap();
+        let result = single_ticket.try_apply_transaction(close.clone());
+        assert_eq!(result, Err(Error::InvalidTransaction(offset + 1)));
+
+        let open = source.value(offset + 0).unwrap().unwrap();
+        assert_eq!(single_ticket.try_apply_transaction(open), Ok(()));
+        let open_other = source.value(offset + 2).unwrap().unwrap();
+        let result = single_ticket.try_apply_transaction(open_other);
+        assert_eq!(result, Err(Error::InvalidTransaction(offset + 2)));
+
+        let close_other = source.value(offset + 3).unwrap().unwrap();
+        let result = single_ticket.try_apply_transaction(close_other);
+        assert_eq!(result, Err(Error::InvalidTicketId(TicketId(4))));
+        assert_eq!(single_ticket.as_position(), SimplePosition::Long);
+
+        assert_eq!(single_ticket.try_apply_transaction(close), Ok(()));
+        assert_eq!(single_ticket.as_position(), SimplePosition::Nothing);
+    }
+
+    #[test]
+    fn test_ticket_book_hedging() {
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let mut book = TicketBook::new(NettingMode::Hedging);
+        book.apply_transaction(open(offset + 0, time, TicketId(1), 100, 1.0, Long));
+        book.apply_transaction(open(offset + 1, time, TicketId(2), 300, 2.0, Long));
+        book.apply_transaction(open(offset + 2, time, TicketId(3), 100, 1.5, Short));
+        assert_eq!(book.tickets().len(), 3);
+        assert_eq!(book.net_unit(), 300);
+        assert_eq!(book.as_position(), SimplePosition::Long);
+        assert_eq!(book.average_price(Long), Some(1.75));
+        assert_eq!(book.average_price(Short), Some(1.5));
+        assert_eq!(
+            book.unrealized_pls(2.0),
+            vec![
+                (TicketId(1), 100.0),
+                (TicketId(2), 0.0),
+                (TicketId(3), -50.0)
+            ]
+        );
+
+        // 一部決済
+        book.apply_transaction(close(offset + 3, offset + 1, time, TicketId(2), 200, 2.5));
+        assert_eq!(book.ticket(TicketId(2)).unwrap().unit, 100);
+        assert_eq!(book.net_unit(), 100);
+
+        let result =
+            book.try_apply_transaction(close(offset + 4, offset + 1, time, TicketId(2), 200, 2.5));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(4))));
+        let result =
+            book.try_apply_transaction(close(offset + 5, offset + 0, time, TicketId(9), 100, 2.5));
+        assert_eq!(result, Err(Error::InvalidTicketId(TicketId(9))));
+        let result =
+            book.try_apply_transaction(open(offset + 6, time, TicketId(3), 100, 2.5, Long));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(6))));
+        assert_eq!(book.open_id(TicketId(2)), Some(TransactionId(1)));
+
+        book.apply_transaction(close(offset + 7, offset + 0, time, TicketId(1), 100, 2.5));
+        book.apply_transaction(close(offset + 8, offset + 1, time, TicketId(2), 100, 2.5));
+        book.apply_transaction(close(offset + 9, offset + 2, time, TicketId(3), 100, 2.5));
+        assert!(book.is_empty());
+        assert_eq!(book.as_position(), SimplePosition::Nothing);
+    }
+
+    #[test]
+    fn test_ticket_book_fifo() {
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let mut book = TicketBook::new(NettingMode::Fifo);
+        book.apply_transaction(open(offset + 0, time, TicketId(1), 100, 1.0, Long));
+        book.apply_transaction(open(offset + 1, time, TicketId(2), 100, 2.0, Long));
+
+        // 古い ticket から決済しなければならない
+        let result =
+            book.try_apply_transaction(close(offset + 2, offset + 1, time, TicketId(2), 100, 2.5));
+        assert_eq!(result, Err(Error::InvalidTicketId(TicketId(2))));
+
+        // 反対方向の ticket が残っている間は open できない
+        let result =
+            book.try_apply_transaction(open(offset + 3, time, TicketId(3), 150, 3.0, Short));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(3))));
+        assert_eq!(book.tickets().len(), 2);
+        assert_eq!(book.net_unit(), 200);
+
+        book.apply_transaction(close(offset + 4, offset + 0, time, TicketId(1), 100, 3.0));
+        book.apply_transaction(close(offset + 5, offset + 1, time, TicketId(2), 100, 3.0));
+        book.apply_transaction(open(offset + 6, time, TicketId(4), 80, 3.0, Short));
+        assert_eq!(book.tickets().len(), 1);
+        assert_eq!(book.ticket(TicketId(4)).unwrap().unit, 80);
+        assert_eq!(book.as_position(), SimplePosition::Short);
+        assert_eq!(book.average_price(Short), Some(3.0));
+        assert_eq!(book.average_price(Long), None);
+    }
+
+    #[test]
+    fn test_partial_close_and_scale_in() {
+        let offset = TransactionId(0);
+        let time = Time::<S5>::new(0);
+        let mut single_ticket = SingleSimpleTicket::new();
+        single_ticket.apply_transaction(open(offset + 0, time, TicketId(1), 100, 1.0, Long));
+        single_ticket.apply_transaction(open(offset + 1, time, TicketId(1), 300, 2.0, Long));
+        let ticket = single_ticket.ticket().unwrap();
+        assert_eq!(ticket.unit, 400);
+        assert_eq!(ticket.price, 1.75);
+
+        single_ticket.apply_transaction(close(offset + 2, offset + 0, time, TicketId(1), 150, 2.0));
+        assert_eq!(single_ticket.ticket().unwrap().unit, 250);
+        let result = single_ticket.try_apply_transaction(close(
+            offset + 3,
+            offset + 0,
+            time,
+            TicketId(1),
+            300,
+            2.0,
+        ));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(3))));
+        single_ticket.apply_transaction(close(offset + 4, offset + 0, time, TicketId(1), 250, 2.0));
+        assert_eq!(single_ticket.ticket(), None);
+
+        let mut book = TicketBook::new(NettingMode::Fifo);
+        book.apply_transaction(open(offset + 0, time, TicketId(1), 100, 1.0, Long));
+        book.apply_transaction(open(offset + 1, time, TicketId(1), 100, 2.0, Long));
+        assert_eq!(book.tickets().len(), 1);
+        assert_eq!(book.average_price(Long), Some(1.5));
+        assert_eq!(book.open_id(TicketId(1)), Some(TransactionId(0)));
+        let result =
+            book.try_apply_transaction(open(offset + 2, time, TicketId(1), 100, 2.0, Short));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(2))));
+        let result =
+            book.try_apply_transaction(open(offset + 3, time, TicketId(2), 200, 2.0, Short));
+        assert_eq!(result, Err(Error::InvalidTransaction(TransactionId(3))));
+        book.apply_transaction(close(offset + 4, offset + 0, time, TicketId(1), 200, 2.0));
+        assert!(book.is_empty());
+        assert_eq!(book.open_id(TicketId(1)), None);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::granularity::*;
 use crate::seq::*;
+use crate::Error;
 use chrono::prelude::*;
 use std::ops::Add;
 use std::ops::Sub;
@@ -16,16 +17,24 @@ where
         Time(t, std::marker::PhantomData)
     }
 
+    pub fn try_new(t: i64) -> Result<Self, Error> {
+        if G::is_valid(t) {
+            Ok(Time(t, std::marker::PhantomData))
+        } else {
+            Err(Error::InvalidTime(t))
+        }
+    }
+
     pub fn timestamp(&self) -> i64 {
         self.0
     }
 
-    pub fn try_into<G2: StaticGranularity>(self) -> Result<Time<G2>, ()> {
-        if G2::is_valid(self.0) {
-            Ok(Time::new(self.0))
-        } else {
-            Err(())
-        }
+    pub fn truncate(t: i64) -> Self {
+        Time::new(G::truncate(t))
+    }
+
+    pub fn try_into<G2: StaticGranularity>(self) -> Result<Time<G2>, Error> {
+        Time::try_new(self.0)
     }
 
     // pub fn range_to_end(&self, end: Time<G>) -> TimeRangeTo<G> {
@@ -41,7 +50,7 @@ where
     G: StaticGranularity,
 {
     fn distance_from(&self, offset: &Time<G>) -> i64 {
-        (self.0 - offset.0) / G::unit_duration()
+        G::distance(self.0, offset.0)
     }
 }
 
@@ -60,14 +69,9 @@ impl<G> std::convert::TryFrom<DateTime<Utc>> for Time<G>
 where
     G: StaticGranularity,
 {
-    type Error = ();
+    type Error = Error;
     fn try_from(dt: DateTime<Utc>) -> Result<Self, Self::Error> {
-        let t = dt.timestamp();
-        if G::is_valid(t) {
-            Ok(Time(t, std::marker::PhantomData))
-        } else {
-            Err(())
-        }
+        Time::try_new(dt.timestamp())
     }
 }
 
@@ -93,7 +97,7 @@ where
 {
     type Output = Time<G>;
     fn add(self, other: i64) -> Self::Output {
-        Time::new(self.0 + G::unit_duration() * other)
+        Time::new(G::add(self.0, other))
     }
 }
 
@@ -103,7 +107,7 @@ where
 {
     type Output = Time<G>;
     fn sub(self, other: i64) -> Self::Output {
-        Time::new(self.0 - G::unit_duration() * other)
+        Time::new(G::add(self.0, -other))
     }
 }
 
@@ -120,6 +124,14 @@ pub mod ffi {
             GTime(t, g)
         }
 
+        pub fn try_new(t: i64, g: Var) -> Result<Self, Error> {
+            if g.is_valid(t) {
+                Ok(GTime(t, g))
+            } else {
+                Err(Error::InvalidTime(t))
+            }
+        }
+
         pub fn timestamp(&self) -> i64 {
             self.0
         }
@@ -128,12 +140,8 @@ pub mod ffi {
             self.1
         }
 
-        pub fn try_into(self, g2: Var) -> Result<GTime<Var>, ()> {
-            if g2.is_valid(self.0) {
-                Ok(GTime::new(self.0, g2))
-            } else {
-                Err(())
-            }
+        pub fn try_into(self, g2: Var) -> Result<GTime<Var>, Error> {
+            GTime::try_new(self.0, g2)
         }
 
         // pub fn range_to_end(&self, end: GTime<Var>) -> TimeRangeTo<Var> {
@@ -259,12 +267,81 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn test_truncate() {
+        let dt = "2019-01-01T01:02:03Z".parse::<DateTime<Utc>>().unwrap();
+        let result = Time::<M1>::truncate(dt.timestamp());
+        let expect = "2019-01-01T01:02:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(result, Time::<M1>::new(expect.timestamp()));
+    }
+
+    #[test]
+    fn test_new_h4() {
+        let dt = "2019-01-01T04:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!(H4::is_valid(dt.timestamp()));
+        let dt = "2019-01-01T05:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!(!H4::is_valid(dt.timestamp()));
+    }
+
+    #[test]
+    fn test_week() {
+        // 2019-01-03 は木曜日
+        let dt = "2019-01-03T12:34:56Z".parse::<DateTime<Utc>>().unwrap();
+        let mon = "2018-12-31T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let sun = "2018-12-30T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            Time::<W1>::truncate(dt.timestamp()).timestamp(),
+            mon.timestamp()
+        );
+        assert_eq!(
+            Time::<W1<Sun>>::truncate(dt.timestamp()).timestamp(),
+            sun.timestamp()
+        );
+        assert!(!W1::<Sun>::is_valid(mon.timestamp()));
+
+        let t = Time::<W1<Sun>>::new(sun.timestamp());
+        let expect = "2019-01-06T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!((t + 1).timestamp(), expect.timestamp());
+    }
+
+    #[test]
+    fn test_month() {
+        let dt = "2019-11-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let t = Time::<MN>::new(dt.timestamp());
+
+        let expect = "2020-02-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!((t + 3).timestamp(), expect.timestamp());
+        let expect = "2019-10-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!((t - 1).timestamp(), expect.timestamp());
+        assert_eq!((t + 15).distance_from(&t), 15);
+
+        let dt = "2020-02-29T23:59:59Z".parse::<DateTime<Utc>>().unwrap();
+        let expect = "2020-02-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            Time::<MN>::truncate(dt.timestamp()).timestamp(),
+            expect.timestamp()
+        );
+    }
+
     #[test]
     fn test_conv_ng() {
         let dt = "2019-01-01T01:00:05Z".parse::<DateTime<Utc>>().unwrap();
         let t = Time::<S5>::new(dt.timestamp());
         let result = t.try_into::<D1>();
-        let expect = Err(());
+        let expect = Err(Error::InvalidTime(dt.timestamp()));
         assert_eq!(result, expect);
     }
+
+    #[test]
+    fn test_try_new() {
+        let dt = "2019-01-01T01:00:05Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            Time::<S5>::try_new(dt.timestamp()),
+            Ok(Time::<S5>::new(dt.timestamp()))
+        );
+        assert_eq!(
+            Time::<M1>::try_new(dt.timestamp()),
+            Err(Error::InvalidTime(dt.timestamp()))
+        );
+    }
 }
@@ -328,20 +328,20 @@ use crate::ticket::*;
 // // }
 
 pub mod order {
-    #[derive(Clone)]
+    #[derive(Clone, PartialEq, Debug)]
     pub struct SimpleTakeProfitOrder {
         pub price: f64,
     }
-    #[derive(Clone)]
+    #[derive(Clone, PartialEq, Debug)]
     pub struct SimpleStopLossOrder {
         pub price: f64,
     }
-    #[derive(Clone)]
+    #[derive(Clone, PartialEq, Debug)]
     pub struct SimpleTrailingStopLossOrder {
         pub distance: f64,
     }
 
-    #[derive(Clone)]
+    #[derive(Clone, PartialEq, Debug)]
     pub enum SimpleOrder {
         TakeProfit(SimpleTakeProfitOrder),
         StopLoss(SimpleStopLossOrder),
@@ -398,6 +398,8 @@ pub struct OpenOrderFillTransaction {
     pub id: TransactionId,
     pub time: DateTime<Utc>,
     pub ticket: SimpleTicket,
+    // account 通貨建ての手数料
+    pub commission: f64,
     // pub time: DateTime<Utc>,
     // pub unit: usize,
     // pub price: f64,
@@ -413,6 +415,7 @@ pub struct CloseOrderFillTransaction {
     pub ticket_id: TicketId,
     pub unit: usize,
     pub price: f64,
+    pub commission: f64,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -445,3 +448,80 @@ impl Transaction for SimpleTransaction {
         }
     }
 }
+
+impl SimpleTransaction {
+    pub fn commission(&self) -> f64 {
+        match self {
+            SimpleTransaction::OpenOrderFill(t) => t.commission,
+            SimpleTransaction::CloseOrderFill(t) => t.commission,
+            SimpleTransaction::Other(_) => 0.0,
+        }
+    }
+}
+
+// テストで使う transaction を組み立てる
+#[cfg(test)]
+pub mod builder {
+    use super::*;
+
+    pub fn open(
+        id: TransactionId,
+        time: impl Into<DateTime<Utc>>,
+        ticket_id: TicketId,
+        unit: usize,
+        price: f64,
+        long_or_short: LongOrShort,
+    ) -> SimpleTransaction {
+        let time = time.into();
+        SimpleTransaction::OpenOrderFill(OpenOrderFillTransaction {
+            id: id,
+            time: time,
+            ticket: SimpleTicket {
+                id: ticket_id,
+                open_time: time,
+                unit: unit,
+                price: price,
+                long_or_short: long_or_short,
+            },
+            commission: 0.0,
+        })
+    }
+
+    pub fn close(
+        id: TransactionId,
+        open_id: TransactionId,
+        time: impl Into<DateTime<Utc>>,
+        ticket_id: TicketId,
+        unit: usize,
+        price: f64,
+    ) -> SimpleTransaction {
+        SimpleTransaction::CloseOrderFill(CloseOrderFillTransaction {
+            id: id,
+            open_id: open_id,
+            time: time.into(),
+            ticket_id: ticket_id,
+            unit: unit,
+            price: price,
+            commission: 0.0,
+        })
+    }
+
+    pub fn with_commission(
+        mut transaction: SimpleTransaction,
+        commission: f64,
+    ) -> SimpleTransaction {
+        match &mut transaction {
+            SimpleTransaction::OpenOrderFill(t) => t.commission = commission,
+            SimpleTransaction::CloseOrderFill(t) => t.commission = commission,
+            SimpleTransaction::Other(_) => (),
+        }
+        transaction
+    }
+
+    pub fn other(id: TransactionId, time: impl Into<DateTime<Utc>>) -> SimpleTransaction {
+        SimpleTransaction::Other(OtherTransaction {
+            id: id,
+            time: time.into(),
+        })
+    }
+}